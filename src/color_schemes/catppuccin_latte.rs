@@ -0,0 +1,30 @@
+use crate::config::ColorSettings;
+
+pub fn get_colors() -> ColorSettings {
+    let mut settings = ColorSettings::default();
+    settings.background = Some("rgba(239, 241, 245, 1.0)".to_string());
+    settings.foreground = Some("rgba(76, 79, 105, 1.0)".to_string());
+    settings.cursor = Some("rgba(220, 138, 120, 1.0)".to_string());
+    settings.cursor_foreground = Some("rgba(239, 241, 245, 1.0)".to_string());
+    settings.selection = Some("rgba(172, 176, 190, 1.0)".to_string());
+    settings.selection_foreground = Some("rgba(76, 79, 105, 1.0)".to_string());
+    settings.palette = vec![
+        Some("rgba(92, 95, 119, 1.0)".to_string()),
+        Some("rgba(210, 15, 57, 1.0)".to_string()),
+        Some("rgba(64, 160, 43, 1.0)".to_string()),
+        Some("rgba(223, 142, 29, 1.0)".to_string()),
+        Some("rgba(30, 102, 245, 1.0)".to_string()),
+        Some("rgba(234, 118, 203, 1.0)".to_string()),
+        Some("rgba(23, 146, 153, 1.0)".to_string()),
+        Some("rgba(172, 176, 190, 1.0)".to_string()),
+        Some("rgba(108, 111, 133, 1.0)".to_string()),
+        Some("rgba(210, 15, 57, 1.0)".to_string()),
+        Some("rgba(64, 160, 43, 1.0)".to_string()),
+        Some("rgba(223, 142, 29, 1.0)".to_string()),
+        Some("rgba(30, 102, 245, 1.0)".to_string()),
+        Some("rgba(234, 118, 203, 1.0)".to_string()),
+        Some("rgba(23, 146, 153, 1.0)".to_string()),
+        Some("rgba(188, 192, 204, 1.0)".to_string()),
+    ];
+    settings
+}
@@ -4,6 +4,10 @@ pub fn get_colors() -> ColorSettings {
     let mut settings = ColorSettings::default();
     settings.background = Some("rgba(30, 30, 46, 1.0)".to_string());
     settings.foreground = Some("rgba(205, 214, 244, 1.0)".to_string());
+    settings.cursor = Some("rgba(245, 224, 220, 1.0)".to_string());
+    settings.cursor_foreground = Some("rgba(30, 30, 46, 1.0)".to_string());
+    settings.selection = Some("rgba(88, 91, 112, 1.0)".to_string());
+    settings.selection_foreground = Some("rgba(205, 214, 244, 1.0)".to_string());
     settings.palette = vec![
         Some("rgba(73, 77, 100, 1.0)".to_string()),
         Some("rgba(243, 139, 168, 1.0)".to_string()),
@@ -0,0 +1,316 @@
+use std::path::Path;
+
+use gtk4::gdk;
+use serde::Serialize;
+
+use crate::color_schemes::user_themes;
+use crate::config::{self, ColorSettings};
+
+const ANSI_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Native,
+    Alacritty,
+    Kitty,
+    WindowsTerminal,
+    Xresources,
+}
+
+impl ExportFormat {
+    pub fn all() -> Vec<ExportFormat> {
+        vec![
+            ExportFormat::Native,
+            ExportFormat::Alacritty,
+            ExportFormat::Kitty,
+            ExportFormat::WindowsTerminal,
+            ExportFormat::Xresources,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Native => "Better Terminal",
+            ExportFormat::Alacritty => "Alacritty",
+            ExportFormat::Kitty => "Kitty",
+            ExportFormat::WindowsTerminal => "Windows Terminal",
+            ExportFormat::Xresources => "Xresources",
+        }
+    }
+
+    /// Suggested file name for a theme called `theme_name`.
+    pub fn file_name(&self, theme_name: &str) -> String {
+        let stem = user_themes::theme_file_stem(theme_name);
+        match self {
+            ExportFormat::Native | ExportFormat::Alacritty => format!("{}.toml", stem),
+            ExportFormat::Kitty => format!("{}.conf", stem),
+            ExportFormat::WindowsTerminal => format!("{}.json", stem),
+            ExportFormat::Xresources => format!("{}.Xresources", stem),
+        }
+    }
+}
+
+/// The colors every format needs, as `#rrggbb`. Settings coming from the
+/// color buttons are stored as `rgb(...)`, which other terminals do not read.
+struct ExportColors {
+    foreground: String,
+    background: String,
+    cursor: Option<String>,
+    cursor_foreground: Option<String>,
+    selection: Option<String>,
+    selection_foreground: Option<String>,
+    bold: Option<String>,
+    palette: Vec<String>,
+}
+
+fn to_hex(role: &str, value: &str) -> Result<String, String> {
+    let rgba = gdk::RGBA::parse(value).map_err(|_| format!("{} is not a valid color: {}", role, value))?;
+    let to_byte = |component: f32| (component.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Ok(format!(
+        "#{:02x}{:02x}{:02x}",
+        to_byte(rgba.red()),
+        to_byte(rgba.green()),
+        to_byte(rgba.blue())
+    ))
+}
+
+impl ExportColors {
+    fn from_settings(colors: &ColorSettings) -> Result<ExportColors, String> {
+        let required = |role: &str, value: &Option<String>| match value {
+            Some(value) => to_hex(role, value),
+            None => Err(format!("{} is not set", role)),
+        };
+        let optional = |role: &str, value: &Option<String>| value.as_deref().map(|value| to_hex(role, value)).transpose();
+
+        let palette = (0..16)
+            .map(|index| required(&format!("color{}", index), colors.palette.get(index).unwrap_or(&None)))
+            .collect::<Result<Vec<String>, String>>()?;
+        Ok(ExportColors {
+            foreground: required("foreground", &colors.foreground)?,
+            background: required("background", &colors.background)?,
+            cursor: optional("cursor", &colors.cursor)?,
+            cursor_foreground: optional("cursor_foreground", &colors.cursor_foreground)?,
+            selection: optional("selection", &colors.selection)?,
+            selection_foreground: optional("selection_foreground", &colors.selection_foreground)?,
+            bold: optional("bold", &colors.bold)?,
+            palette,
+        })
+    }
+
+    fn as_settings(&self) -> ColorSettings {
+        ColorSettings {
+            foreground: Some(self.foreground.clone()),
+            background: Some(self.background.clone()),
+            cursor: self.cursor.clone(),
+            cursor_foreground: self.cursor_foreground.clone(),
+            selection: self.selection.clone(),
+            selection_foreground: self.selection_foreground.clone(),
+            bold: self.bold.clone(),
+            palette: self.palette.iter().cloned().map(Some).collect(),
+            ..ColorSettings::default()
+        }
+    }
+}
+
+/// Renders `colors` as a theme for `format`. Fails when the foreground,
+/// background or any of the 16 palette colors is unset.
+pub fn export_theme(name: &str, colors: &ColorSettings, format: ExportFormat) -> Result<String, String> {
+    let colors = ExportColors::from_settings(colors)?;
+    match format {
+        ExportFormat::Native => user_themes::theme_to_toml(name, &colors.as_settings()),
+        ExportFormat::Alacritty => Ok(to_alacritty(name, &colors)),
+        ExportFormat::Kitty => Ok(to_kitty(name, &colors)),
+        ExportFormat::WindowsTerminal => to_windows_terminal(name, &colors),
+        ExportFormat::Xresources => Ok(to_xresources(name, &colors)),
+    }
+}
+
+pub fn export_theme_file(path: &Path, name: &str, colors: &ColorSettings, format: ExportFormat) -> Result<(), String> {
+    let contents = export_theme(name, colors, format)?;
+    config::write_atomically(path, &contents).map_err(|e| e.to_string())
+}
+
+fn to_alacritty(name: &str, colors: &ExportColors) -> String {
+    let mut out = format!("# {}\n\n", name);
+    out.push_str("[colors.primary]\n");
+    out.push_str(&format!("foreground = \"{}\"\n", colors.foreground));
+    out.push_str(&format!("background = \"{}\"\n", colors.background));
+    // Alacritty names the cursor's own color `cursor`, and the selection's
+    // `background`.
+    for (table, text, background_key, background) in [
+        ("cursor", &colors.cursor_foreground, "cursor", &colors.cursor),
+        ("selection", &colors.selection_foreground, "background", &colors.selection),
+    ] {
+        if text.is_none() && background.is_none() {
+            continue;
+        }
+        out.push_str(&format!("\n[colors.{}]\n", table));
+        if let Some(text) = text {
+            out.push_str(&format!("text = \"{}\"\n", text));
+        }
+        if let Some(background) = background {
+            out.push_str(&format!("{} = \"{}\"\n", background_key, background));
+        }
+    }
+    for (table, offset) in [("normal", 0), ("bright", 8)] {
+        out.push_str(&format!("\n[colors.{}]\n", table));
+        for (index, ansi_name) in ANSI_NAMES.iter().enumerate() {
+            out.push_str(&format!("{} = \"{}\"\n", ansi_name, colors.palette[offset + index]));
+        }
+    }
+    out
+}
+
+fn to_kitty(name: &str, colors: &ExportColors) -> String {
+    let mut out = format!("# {}\n\n", name);
+    out.push_str(&format!("foreground {}\n", colors.foreground));
+    out.push_str(&format!("background {}\n", colors.background));
+    let optional = [
+        ("cursor", &colors.cursor),
+        ("cursor_text_color", &colors.cursor_foreground),
+        ("selection_background", &colors.selection),
+        ("selection_foreground", &colors.selection_foreground),
+    ];
+    for (key, color) in optional {
+        if let Some(color) = color {
+            out.push_str(&format!("{} {}\n", key, color));
+        }
+    }
+    out.push('\n');
+    for (index, color) in colors.palette.iter().enumerate() {
+        out.push_str(&format!("color{} {}\n", index, color));
+    }
+    out
+}
+
+/// One entry for the `schemes` list in Windows Terminal's settings.json.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WindowsTerminalScheme<'a> {
+    name: &'a str,
+    foreground: &'a str,
+    background: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor_color: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selection_background: Option<&'a str>,
+    black: &'a str,
+    red: &'a str,
+    green: &'a str,
+    yellow: &'a str,
+    blue: &'a str,
+    purple: &'a str,
+    cyan: &'a str,
+    white: &'a str,
+    bright_black: &'a str,
+    bright_red: &'a str,
+    bright_green: &'a str,
+    bright_yellow: &'a str,
+    bright_blue: &'a str,
+    bright_purple: &'a str,
+    bright_cyan: &'a str,
+    bright_white: &'a str,
+}
+
+fn to_windows_terminal(name: &str, colors: &ExportColors) -> Result<String, String> {
+    let palette = &colors.palette;
+    let scheme = WindowsTerminalScheme {
+        name,
+        foreground: &colors.foreground,
+        background: &colors.background,
+        cursor_color: colors.cursor.as_deref(),
+        selection_background: colors.selection.as_deref(),
+        black: &palette[0],
+        red: &palette[1],
+        green: &palette[2],
+        yellow: &palette[3],
+        blue: &palette[4],
+        purple: &palette[5],
+        cyan: &palette[6],
+        white: &palette[7],
+        bright_black: &palette[8],
+        bright_red: &palette[9],
+        bright_green: &palette[10],
+        bright_yellow: &palette[11],
+        bright_blue: &palette[12],
+        bright_purple: &palette[13],
+        bright_cyan: &palette[14],
+        bright_white: &palette[15],
+    };
+    let mut out = serde_json::to_string_pretty(&scheme).map_err(|e| e.to_string())?;
+    out.push('\n');
+    Ok(out)
+}
+
+fn to_xresources(name: &str, colors: &ExportColors) -> String {
+    let mut out = format!("! {}\n\n", name);
+    out.push_str(&format!("*.foreground: {}\n", colors.foreground));
+    out.push_str(&format!("*.background: {}\n", colors.background));
+    let optional = [
+        ("cursorColor", &colors.cursor),
+        ("cursorColor2", &colors.cursor_foreground),
+        ("highlightColor", &colors.selection),
+        ("highlightTextColor", &colors.selection_foreground),
+        ("colorBD", &colors.bold),
+    ];
+    for (resource, color) in optional {
+        if let Some(color) = color {
+            out.push_str(&format!("*.{}: {}\n", resource, color));
+        }
+    }
+    out.push('\n');
+    for (index, color) in colors.palette.iter().enumerate() {
+        out.push_str(&format!("*.color{}: {}\n", index, color));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::color_schemes::import::import_theme_file;
+
+    fn sample_colors() -> ColorSettings {
+        let palette = [
+            "#282828", "#cc241d", "#98971a", "#d79921", "#458588", "#b16286", "#689d6a", "#a89984", "#928374",
+            "#fb4934", "#b8bb26", "#fabd2f", "#83a598", "#d3869b", "#8ec07c", "#ebdbb2",
+        ];
+        ColorSettings {
+            foreground: Some("rgb(235,219,178)".to_string()),
+            background: Some("#282828".to_string()),
+            cursor: Some("#fe8019".to_string()),
+            palette: palette.iter().map(|color| Some(color.to_string())).collect(),
+            ..ColorSettings::default()
+        }
+    }
+
+    #[test]
+    fn exported_themes_import_back() {
+        let dir = std::env::temp_dir().join(format!("better-terminal-test-{}-export", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let colors = sample_colors();
+        for format in [ExportFormat::Native, ExportFormat::Alacritty, ExportFormat::Kitty, ExportFormat::Xresources] {
+            let path = dir.join(format.file_name("Gruvbox Dark"));
+            export_theme_file(&path, "Gruvbox Dark", &colors, format).unwrap();
+            let (_, imported) = import_theme_file(&path).unwrap();
+
+            assert_eq!(imported.foreground.as_deref(), Some("#ebdbb2"), "{}", format.name());
+            assert_eq!(imported.background, colors.background, "{}", format.name());
+            assert_eq!(imported.cursor, colors.cursor, "{}", format.name());
+            assert_eq!(imported.palette, colors.palette, "{}", format.name());
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn export_needs_full_palette() {
+        let mut colors = sample_colors();
+        colors.palette[5] = None;
+        assert_eq!(export_theme("Gruvbox", &colors, ExportFormat::Kitty), Err("color5 is not set".to_string()));
+    }
+}
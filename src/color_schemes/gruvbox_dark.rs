@@ -5,6 +5,11 @@ pub fn get_colors() -> ColorSettings {
         foreground: Some("#ebdbb2".to_string()), // fg
         background: Some("#282828".to_string()), // bg
         background_opacity: Some(1.0),
+        cursor: Some("#ebdbb2".to_string()), // fg
+        cursor_foreground: Some("#282828".to_string()), // bg
+        selection: Some("#504945".to_string()), // bg2
+        selection_foreground: Some("#ebdbb2".to_string()), // fg
+        bold: None,
         palette: vec![
             Some("#282828".to_string()), // Normal Black (bg0_h)
             Some("#cc241d".to_string()), // Normal Red
@@ -0,0 +1,33 @@
+use crate::config::ColorSettings;
+
+pub fn get_colors() -> ColorSettings {
+    ColorSettings {
+        foreground: Some("#3c3836".to_string()), // fg
+        background: Some("#fbf1c7".to_string()), // bg
+        background_opacity: Some(1.0),
+        cursor: Some("#3c3836".to_string()), // fg
+        cursor_foreground: Some("#fbf1c7".to_string()), // bg
+        selection: Some("#d5c4a1".to_string()), // bg2
+        selection_foreground: Some("#3c3836".to_string()), // fg
+        bold: None,
+        palette: vec![
+            Some("#fbf1c7".to_string()), // Normal Black (bg0)
+            Some("#cc241d".to_string()), // Normal Red
+            Some("#98971a".to_string()), // Normal Green
+            Some("#d79921".to_string()), // Normal Yellow
+            Some("#458588".to_string()), // Normal Blue
+            Some("#b16286".to_string()), // Normal Magenta
+            Some("#689d6a".to_string()), // Normal Cyan
+            Some("#7c6f64".to_string()), // Normal White (fg4)
+            Some("#928374".to_string()), // Bright Black (gray)
+            Some("#9d0006".to_string()), // Bright Red
+            Some("#79740e".to_string()), // Bright Green
+            Some("#b57614".to_string()), // Bright Yellow
+            Some("#076678".to_string()), // Bright Blue
+            Some("#8f3f71".to_string()), // Bright Magenta
+            Some("#427b58".to_string()), // Bright Cyan
+            Some("#3c3836".to_string()), // Bright White (fg)
+        ],
+        active_preset: Some("GruvboxLight".to_string()),
+    }
+}
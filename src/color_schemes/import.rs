@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::color_schemes::user_themes;
+use crate::config::ColorSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThemeFormat {
+    /// Our own theme file format, as found in the themes directory.
+    Native,
+    AlacrittyToml,
+    AlacrittyYaml,
+    Kitty,
+    ITerm2,
+    Xresources,
+}
+
+impl ThemeFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThemeFormat::Native => "Better Terminal",
+            ThemeFormat::AlacrittyToml | ThemeFormat::AlacrittyYaml => "Alacritty",
+            ThemeFormat::Kitty => "Kitty",
+            ThemeFormat::ITerm2 => "iTerm2",
+            ThemeFormat::Xresources => "Xresources",
+        }
+    }
+}
+
+/// Guesses the format from the file name, falling back to the contents.
+pub fn detect_format(path: &Path, contents: &str) -> Option<ThemeFormat> {
+    let file_name = path.file_name()?.to_str()?.to_lowercase();
+    let extension = path.extension().and_then(|ext| ext.to_str()).map(str::to_lowercase);
+
+    match extension.as_deref() {
+        Some("itermcolors") => return Some(ThemeFormat::ITerm2),
+        Some("yml") | Some("yaml") => return Some(ThemeFormat::AlacrittyYaml),
+        Some("toml") if is_native_theme(contents) => return Some(ThemeFormat::Native),
+        Some("toml") => return Some(ThemeFormat::AlacrittyToml),
+        Some("conf") => return Some(ThemeFormat::Kitty),
+        _ => {}
+    }
+    if file_name.contains("xresources") || file_name.contains("xdefaults") {
+        return Some(ThemeFormat::Xresources);
+    }
+
+    if contents.contains("<plist") {
+        Some(ThemeFormat::ITerm2)
+    } else if contents.lines().any(|line| line.trim_start().starts_with('*') && line.contains(':')) {
+        Some(ThemeFormat::Xresources)
+    } else if contents.lines().any(|line| line.trim_start().starts_with("color0 ")) {
+        Some(ThemeFormat::Kitty)
+    } else {
+        None
+    }
+}
+
+fn is_native_theme(contents: &str) -> bool {
+    contents
+        .parse::<toml::Table>()
+        .is_ok_and(|table| table.contains_key("palette") && !table.contains_key("colors"))
+}
+
+/// Reads a theme written for another terminal. The theme is named after the
+/// file.
+pub fn import_theme_file(path: &Path) -> Result<(String, ColorSettings), String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let contents = String::from_utf8_lossy(&bytes);
+    let format = detect_format(path, &contents)
+        .ok_or_else(|| format!("cannot tell which terminal {} is from", path.display()))?;
+
+    let file_stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("Imported");
+    if format == ThemeFormat::Native {
+        return user_themes::parse_theme(&contents, file_stem);
+    }
+
+    let mut colors = match format {
+        ThemeFormat::Native => unreachable!("handled above"),
+        ThemeFormat::AlacrittyToml => parse_alacritty_toml(&contents)?,
+        ThemeFormat::AlacrittyYaml => parse_alacritty_yaml(&contents)?,
+        ThemeFormat::Kitty => parse_kitty(&contents),
+        ThemeFormat::ITerm2 => parse_iterm2(&bytes)?,
+        ThemeFormat::Xresources => parse_xresources(&contents),
+    };
+
+    check_complete(&colors).map_err(|e| format!("{} theme is incomplete: {}", format.name(), e))?;
+
+    let name = file_stem.to_string();
+    colors.active_preset = Some(name.clone());
+    Ok((name, colors))
+}
+
+/// Imports `path` and saves it into the themes directory, returning the theme
+/// name and the file it was written to. The theme is renamed when its name
+/// is taken, as a built-in would hide it and another theme would be lost.
+pub fn install_theme_file(path: &Path) -> Result<(String, PathBuf), String> {
+    let (name, mut colors) = import_theme_file(path)?;
+    let name = user_themes::unique_theme_name(&name);
+    colors.active_preset = Some(name.clone());
+    let saved_path = user_themes::save_user_theme(&name, &colors)?;
+    Ok((name, saved_path))
+}
+
+fn check_complete(colors: &ColorSettings) -> Result<(), String> {
+    if colors.foreground.is_none() {
+        return Err("no foreground color".to_string());
+    }
+    if colors.background.is_none() {
+        return Err("no background color".to_string());
+    }
+    let missing: Vec<String> = colors
+        .palette
+        .iter()
+        .enumerate()
+        .filter(|(_, color)| color.is_none())
+        .map(|(index, _)| format!("color{}", index))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("missing {}", missing.join(", ")));
+    }
+    Ok(())
+}
+
+/// Accepts `#rgb`, `#rrggbb` and Alacritty's `0xrrggbb`; anything else (such
+/// as Alacritty's `CellForeground`) is not a color.
+fn normalize_color(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches(|c| c == '\'' || c == '"');
+    let hex = value
+        .strip_prefix('#')
+        .or_else(|| value.strip_prefix("0x"))
+        .or_else(|| value.strip_prefix("0X"))?;
+    if matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(format!("#{}", hex.to_lowercase()))
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlacrittyFile {
+    colors: AlacrittyColors,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlacrittyColors {
+    primary: AlacrittyPrimary,
+    cursor: AlacrittyCursor,
+    selection: AlacrittySelection,
+    normal: AlacrittyPalette,
+    bright: AlacrittyPalette,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlacrittyPrimary {
+    foreground: Option<String>,
+    background: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlacrittyCursor {
+    text: Option<String>,
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlacrittySelection {
+    text: Option<String>,
+    background: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlacrittyPalette {
+    black: Option<String>,
+    red: Option<String>,
+    green: Option<String>,
+    yellow: Option<String>,
+    blue: Option<String>,
+    magenta: Option<String>,
+    cyan: Option<String>,
+    white: Option<String>,
+}
+
+impl AlacrittyPalette {
+    fn colors(&self) -> [&Option<String>; 8] {
+        [&self.black, &self.red, &self.green, &self.yellow, &self.blue, &self.magenta, &self.cyan, &self.white]
+    }
+}
+
+fn alacritty_colors(file: AlacrittyFile) -> ColorSettings {
+    let colors = file.colors;
+    let color = |value: &Option<String>| value.as_deref().and_then(normalize_color);
+
+    let mut settings = ColorSettings {
+        foreground: color(&colors.primary.foreground),
+        background: color(&colors.primary.background),
+        cursor: color(&colors.cursor.cursor),
+        cursor_foreground: color(&colors.cursor.text),
+        selection: color(&colors.selection.background),
+        selection_foreground: color(&colors.selection.text),
+        ..ColorSettings::default()
+    };
+    for (index, value) in colors.normal.colors().into_iter().chain(colors.bright.colors()).enumerate() {
+        settings.palette[index] = color(value);
+    }
+    settings
+}
+
+pub fn parse_alacritty_toml(contents: &str) -> Result<ColorSettings, String> {
+    let file: AlacrittyFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+    Ok(alacritty_colors(file))
+}
+
+pub fn parse_alacritty_yaml(contents: &str) -> Result<ColorSettings, String> {
+    let file: AlacrittyFile = serde_yaml::from_str(contents).map_err(|e| e.to_string())?;
+    Ok(alacritty_colors(file))
+}
+
+/// `color0` to `color15`.
+fn palette_index(key: &str) -> Option<usize> {
+    key.strip_prefix("color")?.parse::<usize>().ok().filter(|&index| index < 16)
+}
+
+/// Kitty themes are `name value` lines, e.g. `color1 #cc241d`.
+pub fn parse_kitty(contents: &str) -> ColorSettings {
+    let mut settings = ColorSettings::default();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let Some(color) = normalize_color(value) else {
+            continue;
+        };
+        match key {
+            "foreground" => settings.foreground = Some(color),
+            "background" => settings.background = Some(color),
+            "cursor" => settings.cursor = Some(color),
+            "cursor_text_color" => settings.cursor_foreground = Some(color),
+            "selection_background" => settings.selection = Some(color),
+            "selection_foreground" => settings.selection_foreground = Some(color),
+            key => {
+                if let Some(index) = palette_index(key) {
+                    settings.palette[index] = Some(color);
+                }
+            }
+        }
+    }
+    settings
+}
+
+/// `.itermcolors` files are property lists holding one dictionary of float
+/// components per color.
+pub fn parse_iterm2(bytes: &[u8]) -> Result<ColorSettings, String> {
+    let plist = plist::Value::from_reader(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+    let root = plist.as_dictionary().ok_or("not an iTerm2 color preset")?;
+
+    let color = |key: &str| -> Option<String> {
+        let entry = root.get(key)?.as_dictionary()?;
+        let component = |name: &str| entry.get(name).and_then(plist::Value::as_real).unwrap_or(0.0);
+        let to_byte = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Some(format!(
+            "#{:02x}{:02x}{:02x}",
+            to_byte(component("Red Component")),
+            to_byte(component("Green Component")),
+            to_byte(component("Blue Component"))
+        ))
+    };
+
+    let mut settings = ColorSettings {
+        foreground: color("Foreground Color"),
+        background: color("Background Color"),
+        cursor: color("Cursor Color"),
+        cursor_foreground: color("Cursor Text Color"),
+        selection: color("Selection Color"),
+        selection_foreground: color("Selected Text Color"),
+        bold: color("Bold Color"),
+        ..ColorSettings::default()
+    };
+    for (index, entry) in settings.palette.iter_mut().enumerate() {
+        *entry = color(&format!("Ansi {} Color", index));
+    }
+    Ok(settings)
+}
+
+/// Reads `*.foreground`, `*color4`, `URxvt.cursorColor` style resources.
+/// `#define` names are substituted, which base16 Xresources themes rely on.
+pub fn parse_xresources(contents: &str) -> ColorSettings {
+    let mut defines: HashMap<String, String> = HashMap::new();
+    let mut settings = ColorSettings::default();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if let Some(define) = line.strip_prefix("#define") {
+            let mut parts = define.split_whitespace();
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                defines.insert(name.to_string(), value.to_string());
+            }
+            continue;
+        }
+        if line.starts_with('!') || line.starts_with('#') {
+            continue;
+        }
+        let Some((resource, value)) = line.split_once(':') else {
+            continue;
+        };
+
+        let value = value.trim();
+        let value = defines.get(value).map(String::as_str).unwrap_or(value);
+        let Some(color) = normalize_color(value) else {
+            continue;
+        };
+
+        let name = resource.trim().rsplit(['.', '*']).next().unwrap_or("");
+        match name {
+            "foreground" => settings.foreground = Some(color),
+            "background" => settings.background = Some(color),
+            "cursorColor" => settings.cursor = Some(color),
+            "cursorColor2" => settings.cursor_foreground = Some(color),
+            "highlightColor" => settings.selection = Some(color),
+            "highlightTextColor" => settings.selection_foreground = Some(color),
+            "colorBD" => settings.bold = Some(color),
+            name => {
+                if let Some(index) = palette_index(name) {
+                    settings.palette[index] = Some(color);
+                }
+            }
+        }
+    }
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_format_from_extension_and_contents() {
+        let native = "name = \"Gruvbox\"\npalette = []\n";
+        let alacritty = "[colors.primary]\nforeground = \"#ebdbb2\"\n";
+        assert_eq!(detect_format(Path::new("gruvbox.toml"), native), Some(ThemeFormat::Native));
+        assert_eq!(detect_format(Path::new("gruvbox.toml"), alacritty), Some(ThemeFormat::AlacrittyToml));
+        assert_eq!(detect_format(Path::new("gruvbox.yml"), ""), Some(ThemeFormat::AlacrittyYaml));
+        assert_eq!(detect_format(Path::new("gruvbox.conf"), ""), Some(ThemeFormat::Kitty));
+        assert_eq!(detect_format(Path::new("Gruvbox.itermcolors"), ""), Some(ThemeFormat::ITerm2));
+        assert_eq!(detect_format(Path::new(".Xresources"), ""), Some(ThemeFormat::Xresources));
+
+        assert_eq!(detect_format(Path::new("gruvbox"), "<?xml?>\n<plist version=\"1.0\">"), Some(ThemeFormat::ITerm2));
+        assert_eq!(detect_format(Path::new("gruvbox"), "*.color0: #282828\n"), Some(ThemeFormat::Xresources));
+        assert_eq!(detect_format(Path::new("gruvbox"), "color0 #282828\n"), Some(ThemeFormat::Kitty));
+        assert_eq!(detect_format(Path::new("gruvbox"), "nothing to see"), None);
+    }
+
+    #[test]
+    fn parses_alacritty_toml() {
+        let colors = parse_alacritty_toml(
+            r##"
+[colors.primary]
+foreground = "#EBDBB2"
+background = "0x282828"
+
+[colors.cursor]
+text = "CellBackground"
+cursor = "#fe8019"
+
+[colors.normal]
+black = "#282828"
+red = "#cc241d"
+
+[colors.bright]
+black = "#928374"
+white = "#ebdbb2"
+"##,
+        )
+        .unwrap();
+        assert_eq!(colors.foreground.as_deref(), Some("#ebdbb2"));
+        assert_eq!(colors.background.as_deref(), Some("#282828"));
+        assert_eq!(colors.cursor.as_deref(), Some("#fe8019"));
+        assert_eq!(colors.cursor_foreground, None);
+        assert_eq!(colors.palette[0].as_deref(), Some("#282828"));
+        assert_eq!(colors.palette[1].as_deref(), Some("#cc241d"));
+        assert_eq!(colors.palette[2], None);
+        assert_eq!(colors.palette[8].as_deref(), Some("#928374"));
+        assert_eq!(colors.palette[15].as_deref(), Some("#ebdbb2"));
+    }
+
+    #[test]
+    fn parses_alacritty_yaml() {
+        let colors = parse_alacritty_yaml(
+            r#"
+colors:
+  primary:
+    foreground: '0xebdbb2'
+    background: '#282828'
+  selection:
+    text: '#282828'
+    background: '#504945'
+  normal:
+    blue: '#458588'
+  bright:
+    cyan: '#8ec07c'
+"#,
+        )
+        .unwrap();
+        assert_eq!(colors.foreground.as_deref(), Some("#ebdbb2"));
+        assert_eq!(colors.background.as_deref(), Some("#282828"));
+        assert_eq!(colors.selection.as_deref(), Some("#504945"));
+        assert_eq!(colors.selection_foreground.as_deref(), Some("#282828"));
+        assert_eq!(colors.palette[4].as_deref(), Some("#458588"));
+        assert_eq!(colors.palette[14].as_deref(), Some("#8ec07c"));
+    }
+
+    #[test]
+    fn parses_kitty() {
+        let colors = parse_kitty(
+            "# Gruvbox\n\
+             foreground   #ebdbb2\n\
+             background #282828\n\
+             cursor_text_color background\n\
+             selection_background #504945\n\
+             color0 #282828\n\
+             color9 #fb4934\n\
+             color16 #ffffff\n",
+        );
+        assert_eq!(colors.foreground.as_deref(), Some("#ebdbb2"));
+        assert_eq!(colors.background.as_deref(), Some("#282828"));
+        assert_eq!(colors.cursor_foreground, None);
+        assert_eq!(colors.selection.as_deref(), Some("#504945"));
+        assert_eq!(colors.palette[0].as_deref(), Some("#282828"));
+        assert_eq!(colors.palette[9].as_deref(), Some("#fb4934"));
+        assert_eq!(colors.palette.iter().flatten().count(), 2);
+    }
+
+    #[test]
+    fn parses_iterm2() {
+        let plist = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Ansi 1 Color</key>
+	<dict>
+		<key>Red Component</key>
+		<real>0.8</real>
+		<key>Green Component</key>
+		<real>0.14117647058823529</real>
+		<key>Blue Component</key>
+		<real>0.11372549019607843</real>
+	</dict>
+	<key>Background Color</key>
+	<dict>
+		<key>Color Space</key>
+		<string>sRGB</string>
+		<key>Red Component</key>
+		<real>0.15686274509803921</real>
+		<key>Green Component</key>
+		<real>0.15686274509803921</real>
+		<key>Blue Component</key>
+		<real>0.15686274509803921</real>
+	</dict>
+	<key>Foreground Color</key>
+	<dict>
+		<key>Red Component</key>
+		<real>1.2</real>
+		<key>Green Component</key>
+		<real>0.8588235294117647</real>
+	</dict>
+</dict>
+</plist>
+"#;
+        let colors = parse_iterm2(plist.as_bytes()).unwrap();
+        assert_eq!(colors.palette[1].as_deref(), Some("#cc241d"));
+        assert_eq!(colors.background.as_deref(), Some("#282828"));
+        // Out of range components are clamped and missing ones are zero.
+        assert_eq!(colors.foreground.as_deref(), Some("#ffdb00"));
+        assert_eq!(colors.palette[0], None);
+
+        assert!(parse_iterm2(b"<plist version=\"1.0\"><array/></plist>").is_err());
+    }
+
+    #[test]
+    fn parses_xresources() {
+        let colors = parse_xresources(
+            "! Gruvbox\n\
+             #define base00 #282828\n\
+             #define base08 #fb4934\n\
+             *.foreground: #ebdbb2\n\
+             *background: base00\n\
+             URxvt*cursorColor: #fe8019\n\
+             *.color0: base00\n\
+             URxvt*color1: #cc241d\n\
+             *color9:  base08\n\
+             URxvt.color15: #EBDBB2\n\
+             *.font: Monospace\n",
+        );
+        assert_eq!(colors.foreground.as_deref(), Some("#ebdbb2"));
+        assert_eq!(colors.background.as_deref(), Some("#282828"));
+        assert_eq!(colors.cursor.as_deref(), Some("#fe8019"));
+        assert_eq!(colors.palette[0].as_deref(), Some("#282828"));
+        assert_eq!(colors.palette[1].as_deref(), Some("#cc241d"));
+        assert_eq!(colors.palette[9].as_deref(), Some("#fb4934"));
+        assert_eq!(colors.palette[15].as_deref(), Some("#ebdbb2"));
+        assert_eq!(colors.palette.iter().flatten().count(), 4);
+    }
+}
@@ -1,6 +1,12 @@
 pub mod gruvbox_dark;
+pub mod gruvbox_light;
 pub mod catppuccin_mocha;
+pub mod catppuccin_latte;
 pub mod monokai;
 pub mod nord;
 pub mod tokyo_night;
+pub mod tokyo_night_day;
 pub mod custom;
+pub mod user_themes;
+pub mod import;
+pub mod export;
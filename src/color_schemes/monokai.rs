@@ -4,6 +4,9 @@ pub fn get_colors() -> ColorSettings {
     let mut settings = ColorSettings::default();
     settings.background = Some("rgba(39, 40, 34, 1.0)".to_string());
     settings.foreground = Some("rgba(248, 248, 242, 1.0)".to_string());
+    settings.cursor = Some("rgba(248, 248, 240, 1.0)".to_string());
+    settings.cursor_foreground = Some("rgba(39, 40, 34, 1.0)".to_string());
+    settings.selection = Some("rgba(73, 72, 62, 1.0)".to_string());
     settings.palette = vec![
         Some("rgba(39, 40, 34, 1.0)".to_string()),    // Black
         Some("rgba(249, 38, 114, 1.0)".to_string()),  // Red
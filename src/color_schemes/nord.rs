@@ -4,6 +4,10 @@ pub fn get_colors() -> ColorSettings {
     let mut settings = ColorSettings::default();
     settings.background = Some("rgba(46, 52, 64, 1.0)".to_string()); // Nord0
     settings.foreground = Some("rgba(216, 222, 233, 1.0)".to_string()); // Nord4
+    settings.cursor = Some("rgba(216, 222, 233, 1.0)".to_string()); // Nord4
+    settings.cursor_foreground = Some("rgba(46, 52, 64, 1.0)".to_string()); // Nord0
+    settings.selection = Some("rgba(67, 76, 94, 1.0)".to_string()); // Nord2
+    settings.selection_foreground = Some("rgba(216, 222, 233, 1.0)".to_string()); // Nord4
     settings.palette = vec![
         Some("rgba(59, 66, 82, 1.0)".to_string()),  // Nord1 (Black)
         Some("rgba(191, 97, 106, 1.0)".to_string()), // Nord11 (Red)
@@ -4,6 +4,10 @@ pub fn get_colors() -> ColorSettings {
     let mut settings = ColorSettings::default();
     settings.background = Some("rgba(26, 27, 38, 1.0)".to_string()); // #1a1b26
     settings.foreground = Some("rgba(169, 177, 214, 1.0)".to_string()); // #a9b1d6
+    settings.cursor = Some("rgba(192, 202, 245, 1.0)".to_string()); // #c0caf5
+    settings.cursor_foreground = Some("rgba(26, 27, 38, 1.0)".to_string()); // #1a1b26
+    settings.selection = Some("rgba(40, 52, 87, 1.0)".to_string()); // #283457
+    settings.selection_foreground = Some("rgba(192, 202, 245, 1.0)".to_string()); // #c0caf5
     settings.palette = vec![
         Some("rgba(31, 32, 46, 1.0)".to_string()),  // #1f202e (Black)
         Some("rgba(247, 118, 142, 1.0)".to_string()), // #f7768e (Red)
@@ -0,0 +1,29 @@
+use crate::config::ColorSettings;
+
+pub fn get_colors() -> ColorSettings {
+    let mut settings = ColorSettings::default();
+    settings.background = Some("rgba(225, 226, 231, 1.0)".to_string()); // #e1e2e7
+    settings.foreground = Some("rgba(55, 96, 191, 1.0)".to_string()); // #3760bf
+    settings.cursor = Some("rgba(55, 96, 191, 1.0)".to_string()); // #3760bf
+    settings.cursor_foreground = Some("rgba(225, 226, 231, 1.0)".to_string()); // #e1e2e7
+    settings.selection = Some("rgba(183, 193, 227, 1.0)".to_string()); // #b7c1e3
+    settings.palette = vec![
+        Some("rgba(233, 233, 237, 1.0)".to_string()), // #e9e9ed (Black)
+        Some("rgba(245, 42, 101, 1.0)".to_string()), // #f52a65 (Red)
+        Some("rgba(88, 117, 57, 1.0)".to_string()), // #587539 (Green)
+        Some("rgba(140, 108, 62, 1.0)".to_string()), // #8c6c3e (Yellow)
+        Some("rgba(46, 125, 233, 1.0)".to_string()), // #2e7de9 (Blue)
+        Some("rgba(152, 84, 241, 1.0)".to_string()), // #9854f1 (Magenta)
+        Some("rgba(0, 113, 151, 1.0)".to_string()), // #007197 (Cyan)
+        Some("rgba(97, 114, 176, 1.0)".to_string()), // #6172b0 (White)
+        Some("rgba(161, 166, 197, 1.0)".to_string()), // #a1a6c5 (Bright Black)
+        Some("rgba(245, 42, 101, 1.0)".to_string()), // #f52a65 (Bright Red)
+        Some("rgba(88, 117, 57, 1.0)".to_string()), // #587539 (Bright Green)
+        Some("rgba(140, 108, 62, 1.0)".to_string()), // #8c6c3e (Bright Yellow)
+        Some("rgba(46, 125, 233, 1.0)".to_string()), // #2e7de9 (Bright Blue)
+        Some("rgba(152, 84, 241, 1.0)".to_string()), // #9854f1 (Bright Magenta)
+        Some("rgba(0, 113, 151, 1.0)".to_string()), // #007197 (Bright Cyan)
+        Some("rgba(55, 96, 191, 1.0)".to_string()), // #3760bf (Bright White)
+    ];
+    settings
+}
@@ -0,0 +1,287 @@
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, ColorSchemePreset, ColorSettings};
+
+pub const THEMES_DIR: &str = "themes";
+
+/// A color scheme loaded from a `.toml` file in the themes directory.
+#[derive(Debug, Clone)]
+pub struct UserTheme {
+    pub name: String,
+    pub path: PathBuf,
+    pub colors: ColorSettings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ThemeFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    foreground: String,
+    background: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor_foreground: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selection: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selection_foreground: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bold: Option<String>,
+    palette: Vec<String>,
+}
+
+thread_local! {
+    static USER_THEMES: RefCell<Option<Vec<UserTheme>>> = const { RefCell::new(None) };
+}
+
+/// The `themes` directory next to the config file in use, so an instance
+/// started with `--config` keeps its themes apart.
+pub fn themes_dir() -> Option<PathBuf> {
+    let config_path = config::get_config_path()?;
+    Some(config_path.parent()?.join(THEMES_DIR))
+}
+
+pub fn parse_theme(contents: &str, fallback_name: &str) -> Result<(String, ColorSettings), String> {
+    let file: ThemeFile = toml::from_str(contents).map_err(|e| e.message().to_string())?;
+    if file.palette.len() != 16 {
+        return Err(format!("palette must have 16 colors, found {}", file.palette.len()));
+    }
+
+    let name = file.name.unwrap_or_else(|| fallback_name.to_string());
+    let colors = ColorSettings {
+        foreground: Some(file.foreground),
+        background: Some(file.background),
+        cursor: file.cursor,
+        cursor_foreground: file.cursor_foreground,
+        selection: file.selection,
+        selection_foreground: file.selection_foreground,
+        bold: file.bold,
+        palette: file.palette.into_iter().map(Some).collect(),
+        active_preset: Some(name.clone()),
+        ..ColorSettings::default()
+    };
+    Ok((name, colors))
+}
+
+/// Renders `colors` in the theme file format. Fails when a color the format
+/// requires is unset.
+pub fn theme_to_toml(name: &str, colors: &ColorSettings) -> Result<String, String> {
+    let palette = colors
+        .palette
+        .iter()
+        .enumerate()
+        .map(|(index, color)| color.clone().ok_or_else(|| format!("color{} is not set", index)))
+        .collect::<Result<Vec<String>, String>>()?;
+    let file = ThemeFile {
+        name: Some(name.to_string()),
+        foreground: colors.foreground.clone().ok_or("foreground is not set")?,
+        background: colors.background.clone().ok_or("background is not set")?,
+        cursor: colors.cursor.clone(),
+        cursor_foreground: colors.cursor_foreground.clone(),
+        selection: colors.selection.clone(),
+        selection_foreground: colors.selection_foreground.clone(),
+        bold: colors.bold.clone(),
+        palette,
+    };
+    toml::to_string_pretty(&file).map_err(|e| e.to_string())
+}
+
+/// `name` with characters that do not belong in a file name replaced.
+pub fn theme_file_stem(name: &str) -> String {
+    let file_stem: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' { c } else { '_' })
+        .collect();
+    file_stem.trim().to_string()
+}
+
+fn theme_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.toml", theme_file_stem(name)))
+}
+
+/// `name`, or `name (imported)`, `name (imported 2)` and so on when a
+/// built-in preset, another theme or another theme's file already has it.
+pub fn unique_theme_name(name: &str) -> String {
+    let themes = user_themes();
+    let dir = themes_dir();
+    available_name(name, |candidate| {
+        themes.iter().any(|theme| theme.name == candidate)
+            || dir.as_deref().is_some_and(|dir| theme_path(dir, candidate).exists())
+    })
+}
+
+fn available_name(name: &str, is_taken: impl Fn(&str) -> bool) -> String {
+    let built_in = ColorSchemePreset::built_in_presets();
+    let is_free = |candidate: &str| !is_taken(candidate) && !built_in.iter().any(|preset| preset.name() == candidate);
+    if is_free(name) {
+        return name.to_string();
+    }
+    let mut candidate = format!("{} (imported)", name);
+    let mut number = 2;
+    while !is_free(&candidate) {
+        candidate = format!("{} (imported {})", name, number);
+        number += 1;
+    }
+    candidate
+}
+
+/// Writes `colors` as `<name>.toml` into the themes directory and rescans it,
+/// replacing an existing theme file of the same name.
+pub fn save_user_theme(name: &str, colors: &ColorSettings) -> Result<PathBuf, String> {
+    let dir = themes_dir().ok_or("cannot determine the themes directory")?;
+    let contents = theme_to_toml(name, colors)?;
+
+    let path = theme_path(&dir, name);
+    config::write_atomically(&path, &contents).map_err(|e| e.to_string())?;
+    rescan_user_themes();
+    Ok(path)
+}
+
+fn load_theme_file(path: &Path) -> Result<UserTheme, String> {
+    let contents = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let fallback_name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("Unnamed");
+    let (name, colors) = parse_theme(&contents, fallback_name)?;
+    Ok(UserTheme {
+        name,
+        path: path.to_path_buf(),
+        colors,
+    })
+}
+
+/// Loads the themes in `dir`. Files are read in name order, so of two files
+/// declaring the same theme name, the same one is always skipped.
+fn scan_themes_dir(dir: &Path) -> Vec<UserTheme> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("toml"))
+        .collect();
+    paths.sort();
+
+    let mut themes: Vec<UserTheme> = Vec::new();
+    for path in paths {
+        match load_theme_file(&path) {
+            Ok(theme) if themes.iter().any(|other| other.name == theme.name) => {
+                eprintln!("Skipping theme {}: a theme named \"{}\" already exists", path.display(), theme.name);
+            }
+            Ok(theme) => themes.push(theme),
+            Err(e) => eprintln!("Failed to load theme {}: {}", path.display(), e),
+        }
+    }
+    themes.sort_by_key(|theme| theme.name.to_lowercase());
+    themes
+}
+
+/// Themes found in the themes directory. The directory is scanned on first
+/// use; call `rescan_user_themes` after adding files.
+pub fn user_themes() -> Vec<UserTheme> {
+    USER_THEMES.with(|cache| {
+        cache
+            .borrow_mut()
+            .get_or_insert_with(scan_default_themes_dir)
+            .clone()
+    })
+}
+
+pub fn rescan_user_themes() {
+    USER_THEMES.with(|cache| *cache.borrow_mut() = Some(scan_default_themes_dir()));
+}
+
+fn scan_default_themes_dir() -> Vec<UserTheme> {
+    themes_dir().map(|dir| scan_themes_dir(&dir)).unwrap_or_default()
+}
+
+pub fn find_user_theme(name: &str) -> Option<UserTheme> {
+    user_themes().into_iter().find(|theme| theme.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: &str = r##"palette = [
+    "#282828", "#cc241d", "#98971a", "#d79921", "#458588", "#b16286", "#689d6a", "#a89984",
+    "#928374", "#fb4934", "#b8bb26", "#fabd2f", "#83a598", "#d3869b", "#8ec07c", "#ebdbb2",
+]"##;
+
+    fn theme_contents(name: Option<&str>) -> String {
+        let name_line = name.map(|name| format!("name = \"{}\"\n", name)).unwrap_or_default();
+        format!("{}foreground = \"#ebdbb2\"\nbackground = \"#282828\"\n{}\n", name_line, PALETTE)
+    }
+
+    #[test]
+    fn parses_themes() {
+        let (name, colors) = parse_theme(&theme_contents(Some("Gruvbox Dark")), "gruvbox").unwrap();
+        assert_eq!(name, "Gruvbox Dark");
+        assert_eq!(colors.active_preset.as_deref(), Some("Gruvbox Dark"));
+        assert_eq!(colors.foreground.as_deref(), Some("#ebdbb2"));
+        assert_eq!(colors.palette[15].as_deref(), Some("#ebdbb2"));
+        assert_eq!(colors.cursor, None);
+        assert_eq!(colors.selection, None);
+
+        let with_roles = format!("cursor = \"#fe8019\"\nselection = \"#504945\"\n{}", theme_contents(None));
+        let (name, colors) = parse_theme(&with_roles, "gruvbox").unwrap();
+        assert_eq!(name, "gruvbox");
+        assert_eq!(colors.cursor.as_deref(), Some("#fe8019"));
+        assert_eq!(colors.selection.as_deref(), Some("#504945"));
+        assert_eq!(colors.selection_foreground, None);
+    }
+
+    #[test]
+    fn rejects_incomplete_palettes() {
+        let short = "foreground = \"#ebdbb2\"\nbackground = \"#282828\"\npalette = [\"#000000\"]\n";
+        assert_eq!(parse_theme(short, "short").unwrap_err(), "palette must have 16 colors, found 1");
+        assert!(parse_theme(PALETTE, "no foreground").is_err());
+    }
+
+    #[test]
+    fn themes_round_trip_through_toml() {
+        let (_, colors) = parse_theme(&theme_contents(None), "gruvbox").unwrap();
+        let (name, parsed) = parse_theme(&theme_to_toml("Gruvbox", &colors).unwrap(), "other").unwrap();
+        assert_eq!(name, "Gruvbox");
+        assert_eq!(parsed.palette, colors.palette);
+    }
+
+    #[test]
+    fn file_stems_are_safe() {
+        assert_eq!(theme_file_stem("Gruvbox Dark"), "Gruvbox Dark");
+        assert_eq!(theme_file_stem("Solarized/Light: v2"), "Solarized_Light_ v2");
+        assert_eq!(theme_file_stem(" ../evil "), "___evil");
+    }
+
+    #[test]
+    fn scan_skips_duplicate_names_in_file_order() {
+        let dir = std::env::temp_dir().join(format!("better-terminal-test-{}-themes", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("b.toml"), theme_contents(Some("Shared"))).unwrap();
+        fs::write(dir.join("a.toml"), theme_contents(Some("Shared"))).unwrap();
+        fs::write(dir.join("Zenburn.toml"), theme_contents(None)).unwrap();
+        fs::write(dir.join("broken.toml"), "palette = 1").unwrap();
+        fs::write(dir.join("notes.txt"), "not a theme").unwrap();
+
+        let themes = scan_themes_dir(&dir);
+        let found: Vec<(&str, PathBuf)> = themes.iter().map(|theme| (theme.name.as_str(), theme.path.clone())).collect();
+        assert_eq!(found, vec![("Shared", dir.join("a.toml")), ("Zenburn", dir.join("Zenburn.toml"))]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn imported_names_do_not_clash() {
+        let taken = ["Solarized", "Solarized (imported)"];
+        let is_taken = |name: &str| taken.contains(&name);
+        assert_eq!(available_name("Dracula", is_taken), "Dracula");
+        assert_eq!(available_name("Nord", is_taken), "Nord (imported)");
+        assert_eq!(available_name("Custom", is_taken), "Custom (imported)");
+        assert_eq!(available_name("Solarized", is_taken), "Solarized (imported 2)");
+    }
+}
@@ -1,64 +1,121 @@
+use std::collections::BTreeMap;
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-use crate::color_schemes;
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::color_schemes::{self, user_themes};
 
 pub const CONFIG_DIR: &str = ".config/better-terminal";
 pub const CONFIG_FILE: &str = "better-terminal.conf";
+pub const DEFAULT_TITLE_TEMPLATE: &str = "{title}";
+/// Names that can appear in braces in `title_template`.
+pub const TITLE_PLACEHOLDERS: [&str; 3] = ["title", "cwd", "process"];
+/// Editors and REPLs that turn on bracketed paste whenever they run.
+const DEFAULT_BRACKETED_PASTE_PROGRAMS: [&str; 8] = ["vim", "nvim", "emacs", "nano", "micro", "hx", "kak", "ipython"];
+pub const CONFIG_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ColorSchemePreset {
     GruvboxDark,
+    GruvboxLight,
     CatppuccinMocha,
+    CatppuccinLatte,
     Monokai,
     Nord,
     TokyoNight,
+    TokyoNightDay,
     Custom,
+    /// A theme file from the user's themes directory, by theme name.
+    User(String),
 }
 
 impl ColorSchemePreset {
-    pub fn name(&self) -> &'static str {
+    pub fn name(&self) -> &str {
         match self {
             ColorSchemePreset::GruvboxDark => "GruvboxDark",
+            ColorSchemePreset::GruvboxLight => "GruvboxLight",
             ColorSchemePreset::CatppuccinMocha => "CatppuccinMocha",
+            ColorSchemePreset::CatppuccinLatte => "CatppuccinLatte",
             ColorSchemePreset::Monokai => "Monokai",
             ColorSchemePreset::Nord => "Nord",
             ColorSchemePreset::TokyoNight => "TokyoNight",
+            ColorSchemePreset::TokyoNightDay => "TokyoNightDay",
             ColorSchemePreset::Custom => "Custom",
+            ColorSchemePreset::User(name) => name,
         }
     }
 
     pub fn from_name(name: &str) -> Option<Self> {
         match name {
             "GruvboxDark" => Some(ColorSchemePreset::GruvboxDark),
+            "GruvboxLight" => Some(ColorSchemePreset::GruvboxLight),
             "CatppuccinMocha" => Some(ColorSchemePreset::CatppuccinMocha),
+            "CatppuccinLatte" => Some(ColorSchemePreset::CatppuccinLatte),
             "Monokai" => Some(ColorSchemePreset::Monokai),
             "Nord" => Some(ColorSchemePreset::Nord),
             "TokyoNight" => Some(ColorSchemePreset::TokyoNight),
+            "TokyoNightDay" => Some(ColorSchemePreset::TokyoNightDay),
             "Custom" => Some(ColorSchemePreset::Custom),
-            _ => None,
+            _ => user_themes::find_user_theme(name).map(|theme| ColorSchemePreset::User(theme.name)),
         }
     }
 
-    pub fn all_presets() -> Vec<Self> {
+    pub fn built_in_presets() -> Vec<Self> {
         vec![
             ColorSchemePreset::GruvboxDark,
+            ColorSchemePreset::GruvboxLight,
             ColorSchemePreset::CatppuccinMocha,
+            ColorSchemePreset::CatppuccinLatte,
             ColorSchemePreset::Monokai,
             ColorSchemePreset::Nord,
             ColorSchemePreset::TokyoNight,
+            ColorSchemePreset::TokyoNightDay,
             ColorSchemePreset::Custom,
         ]
     }
+
+    /// Built-in presets followed by the user's theme files. A theme file
+    /// named like a built-in is hidden by it.
+    pub fn all_presets() -> Vec<Self> {
+        let mut presets = Self::built_in_presets();
+        for theme in user_themes::user_themes() {
+            if presets.iter().any(|preset| preset.name() == theme.name) {
+                continue;
+            }
+            presets.push(ColorSchemePreset::User(theme.name));
+        }
+        presets
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ColorSettings {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub foreground: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub background_opacity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    /// Text under the cursor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor_foreground: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection: Option<String>,
+    /// Selected text. Left unset, VTE keeps each cell's own color.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection_foreground: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold: Option<String>,
+    #[serde(with = "palette_format", skip_serializing_if = "palette_is_empty")]
     pub palette: Vec<Option<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub active_preset: Option<String>,
 }
 
@@ -68,59 +125,533 @@ impl Default for ColorSettings {
             foreground: None,
             background: None,
             background_opacity: Some(1.0),
+            cursor: None,
+            cursor_foreground: None,
+            selection: None,
+            selection_foreground: None,
+            bold: None,
             palette: vec![None; 16],
             active_preset: None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+fn palette_is_empty(palette: &[Option<String>]) -> bool {
+    palette.iter().all(Option::is_none)
+}
+
+// TOML arrays cannot hold "no value", so unset palette entries are written
+// as empty strings and read back as `None`.
+mod palette_format {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(palette: &[Option<String>], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(palette.iter().map(|color| color.as_deref().unwrap_or("")))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Option<String>>, D::Error> {
+        let colors = Vec::<String>::deserialize(deserializer)?;
+        let mut palette: Vec<Option<String>> = colors
+            .into_iter()
+            .map(|color| if color.is_empty() { None } else { Some(color) })
+            .collect();
+        palette.resize(16, None);
+        Ok(palette)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct AppSettings {
     pub title_bar_visible: bool,
+    /// Window title, with `{title}`, `{cwd}` and `{process}` filled in from
+    /// the active terminal.
+    pub title_template: String,
+    /// Ask before closing a window while a program other than the shell is
+    /// running in it.
+    pub confirm_close: bool,
+    /// Programs that do not need the confirmation, by process name.
+    pub confirm_close_ignore: Vec<String>,
+    /// Copy selected text to the clipboard too, not only to the PRIMARY
+    /// selection.
+    pub copy_on_select: bool,
+    /// Ask before pasting text that would run commands by itself.
+    pub paste_warning: bool,
+    /// Programs that turn on bracketed paste, by process name.
+    pub bracketed_paste_programs: Vec<String>,
+    /// Follow OSC 8 hyperlinks, which programs such as `ls --hyperlink`
+    /// print with text of their choosing.
+    pub hyperlinks: bool,
+    /// Extra patterns to turn into links, after the built-in URLs and paths.
+    pub link_patterns: Vec<LinkPattern>,
     pub colors: ColorSettings,
     pub font_family: String,
     pub font_size: f64,
+    /// Presets used instead of `colors` while the system prefers a light or
+    /// dark style.
+    pub light_preset: Option<String>,
+    pub dark_preset: Option<String>,
+    pub shell: ShellSettings,
+    /// Profile used for new windows and tabs unless another is picked.
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+    /// Accelerators by action name, replacing the defaults.
+    pub keybindings: BTreeMap<String, Keybinding>,
+}
+
+/// A custom link from `[[links.patterns]]`: text matching `pattern` opens
+/// `url`, with `$0` replaced by the whole match and `$1` to `$9` by its
+/// groups, e.g. `JIRA-(\d+)` to `https://jira.example.com/browse/JIRA-$1`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkPattern {
+    pub pattern: String,
+    pub url: String,
+}
+
+/// The accelerators for one action in `[keybindings]`: a single one such as
+/// `"<Control><Shift>c"`, a list, or `""` or `[]` to unbind the action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Keybinding {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Keybinding {
+    pub fn accels(&self) -> Vec<String> {
+        let accels = match self {
+            Keybinding::One(accel) => std::slice::from_ref(accel),
+            Keybinding::Many(accels) => accels.as_slice(),
+        };
+        accels.iter().filter(|accel| !accel.is_empty()).cloned().collect()
+    }
+}
+
+/// What to run in a new terminal and where. Everything is optional: by
+/// default `$SHELL` starts in the current directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShellSettings {
+    /// Command line to run instead of `$SHELL`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Arguments appended to the command.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+    /// Start `$SHELL` as a login shell. Has no effect on a custom command.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub login: bool,
+    /// Variables set for the child, on top of the inherited environment.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Inherited variables the child should not see.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unset_env: Vec<String>,
+    #[serde(skip_serializing_if = "ExitBehavior::is_default")]
+    pub exit_behavior: ExitBehavior,
+    /// Start the child from `env` alone instead of our environment. Set for
+    /// windows opened on behalf of another process, which sends its own.
+    #[serde(skip)]
+    pub replace_environment: bool,
+}
+
+impl ShellSettings {
+    fn is_default(&self) -> bool {
+        *self == ShellSettings::default()
+    }
+}
+
+/// What a terminal does once its child has exited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExitBehavior {
+    /// Close the pane, and the tab along with its last pane.
+    #[default]
+    Close,
+    /// Keep the output and offer to relaunch the command.
+    Hold,
+    /// Start the command again.
+    Restart,
+}
+
+impl ExitBehavior {
+    fn is_default(&self) -> bool {
+        *self == ExitBehavior::default()
+    }
+}
+
+/// A named set of overrides, e.g. for SSH sessions that should look different
+/// from local shells. Unset fields fall back to the global settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Command line to run instead of the shell from `[shell]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login: Option<bool>,
+    /// Added to the `[shell]` environment.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unset_env: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_behavior: Option<ExitBehavior>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    /// Lines kept in scrollback; negative for unlimited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrollback_lines: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colors: Option<ColorSettings>,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         AppSettings {
             title_bar_visible: true,
+            title_template: DEFAULT_TITLE_TEMPLATE.to_string(),
+            confirm_close: true,
+            confirm_close_ignore: Vec::new(),
+            copy_on_select: false,
+            paste_warning: true,
+            bracketed_paste_programs: DEFAULT_BRACKETED_PASTE_PROGRAMS.iter().map(|name| name.to_string()).collect(),
+            hyperlinks: true,
+            link_patterns: Vec::new(),
             colors: ColorSettings::default(),
             font_family: "Monospace".to_string(),
             font_size: 12.0,
+            light_preset: None,
+            dark_preset: None,
+            shell: ShellSettings::default(),
+            default_profile: None,
+            profiles: BTreeMap::new(),
+            keybindings: BTreeMap::new(),
         }
     }
 }
 
-pub fn get_preset_colors(preset: &ColorSchemePreset) -> ColorSettings {
-    let mut settings = ColorSettings::default();
-    settings.active_preset = Some(preset.name().to_string());
-    match preset {
-        ColorSchemePreset::GruvboxDark => {
-            settings = color_schemes::gruvbox_dark::get_colors();
+impl AppSettings {
+    /// The colors to show for the given system style: the light or dark
+    /// preset when one is configured, otherwise `colors`.
+    pub fn colors_for_style(&self, dark: bool) -> ColorSettings {
+        let preset_name = if dark { &self.dark_preset } else { &self.light_preset };
+        match preset_name.as_deref().and_then(ColorSchemePreset::from_name) {
+            Some(preset) => {
+                let mut colors = get_preset_colors(&preset);
+                colors.background_opacity = self.colors.background_opacity;
+                colors
+            }
+            None => self.colors.clone(),
+        }
+    }
+
+    /// Name of the profile to use when `requested` is asked for, falling back
+    /// to the default profile. Fails with the name when there is no such
+    /// profile, in which case the global settings apply.
+    pub fn profile_name(&self, requested: Option<&str>) -> Result<Option<String>, String> {
+        let Some(name) = requested.or(self.default_profile.as_deref()) else {
+            return Ok(None);
+        };
+        if self.profiles.contains_key(name) {
+            Ok(Some(name.to_string()))
+        } else {
+            Err(name.to_string())
+        }
+    }
+
+    /// The `[shell]` settings with `profile`'s overrides applied. A profile's
+    /// command brings its own arguments rather than appending to the global
+    /// ones.
+    pub fn shell_settings(&self, profile: Option<&str>) -> ShellSettings {
+        let mut shell = self.shell.clone();
+        let Some(profile) = profile.and_then(|name| self.profiles.get(name)) else {
+            return shell;
+        };
+        if profile.command.is_some() {
+            shell.command = profile.command.clone();
+            shell.args = profile.args.clone();
+        } else if !profile.args.is_empty() {
+            shell.args = profile.args.clone();
         }
-        ColorSchemePreset::CatppuccinMocha => {
-            settings = color_schemes::catppuccin_mocha::get_colors();
+        if profile.working_directory.is_some() {
+            shell.working_directory = profile.working_directory.clone();
         }
-        ColorSchemePreset::Monokai => {
-            settings = color_schemes::monokai::get_colors();
+        if let Some(login) = profile.login {
+            shell.login = login;
         }
-        ColorSchemePreset::Nord => {
-            settings = color_schemes::nord::get_colors();
+        if let Some(exit_behavior) = profile.exit_behavior {
+            shell.exit_behavior = exit_behavior;
         }
-        ColorSchemePreset::TokyoNight => {
-            settings = color_schemes::tokyo_night::get_colors();
+        shell.env.extend(profile.env.clone());
+        shell.unset_env.extend(profile.unset_env.iter().cloned());
+        shell
+    }
+
+    /// These settings with `profile`'s font and colors in place of the global
+    /// ones. A profile's colors also replace the light and dark presets.
+    pub fn with_profile(&self, profile: Option<&str>) -> AppSettings {
+        let mut settings = self.clone();
+        let Some(profile) = profile.and_then(|name| self.profiles.get(name)) else {
+            return settings;
+        };
+        if let Some(font_family) = &profile.font_family {
+            settings.font_family = font_family.clone();
         }
-        ColorSchemePreset::Custom => {
-            settings = color_schemes::custom::get_colors();
+        if let Some(font_size) = profile.font_size {
+            settings.font_size = font_size;
         }
+        if let Some(colors) = &profile.colors {
+            settings.colors = colors.clone();
+            settings.light_preset = None;
+            settings.dark_preset = None;
+        }
+        settings
+    }
+}
+
+// On-disk layout of `better-terminal.conf`. `AppSettings` stays flat for the
+// UI code; this mirrors it with one table per concern.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_profile: Option<String>,
+    window: WindowConfig,
+    font: FontConfig,
+    colors: ColorSettings,
+    #[serde(skip_serializing_if = "ShellSettings::is_default")]
+    shell: ShellSettings,
+    #[serde(skip_serializing_if = "AppearanceConfig::is_empty")]
+    appearance: AppearanceConfig,
+    #[serde(skip_serializing_if = "ClipboardConfig::is_default")]
+    clipboard: ClipboardConfig,
+    #[serde(skip_serializing_if = "LinksConfig::is_default")]
+    links: LinksConfig,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Profile>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    keybindings: BTreeMap<String, Keybinding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct WindowConfig {
+    title_bar_visible: bool,
+    title_template: String,
+    confirm_close: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    confirm_close_ignore: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct FontConfig {
+    family: String,
+    size: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct AppearanceConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    light_preset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dark_preset: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct ClipboardConfig {
+    copy_on_select: bool,
+    paste_warning: bool,
+    bracketed_paste_programs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct LinksConfig {
+    hyperlinks: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    patterns: Vec<LinkPattern>,
+}
+
+impl LinksConfig {
+    fn is_default(&self) -> bool {
+        *self == LinksConfig::default()
     }
+}
+
+impl ClipboardConfig {
+    fn is_default(&self) -> bool {
+        *self == ClipboardConfig::default()
+    }
+}
+
+impl AppearanceConfig {
+    fn is_empty(&self) -> bool {
+        self.light_preset.is_none() && self.dark_preset.is_none()
+    }
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        ConfigFile::from(&AppSettings::default())
+    }
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        ConfigFile::default().clipboard
+    }
+}
+
+impl Default for LinksConfig {
+    fn default() -> Self {
+        ConfigFile::default().links
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        ConfigFile::default().window
+    }
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        ConfigFile::default().font
+    }
+}
+
+impl From<&AppSettings> for ConfigFile {
+    fn from(settings: &AppSettings) -> Self {
+        ConfigFile {
+            version: CONFIG_VERSION,
+            default_profile: settings.default_profile.clone(),
+            window: WindowConfig {
+                title_bar_visible: settings.title_bar_visible,
+                title_template: settings.title_template.clone(),
+                confirm_close: settings.confirm_close,
+                confirm_close_ignore: settings.confirm_close_ignore.clone(),
+            },
+            font: FontConfig {
+                family: settings.font_family.clone(),
+                size: settings.font_size,
+            },
+            colors: settings.colors.clone(),
+            shell: settings.shell.clone(),
+            clipboard: ClipboardConfig {
+                copy_on_select: settings.copy_on_select,
+                paste_warning: settings.paste_warning,
+                bracketed_paste_programs: settings.bracketed_paste_programs.clone(),
+            },
+            links: LinksConfig {
+                hyperlinks: settings.hyperlinks,
+                patterns: settings.link_patterns.clone(),
+            },
+            appearance: AppearanceConfig {
+                light_preset: settings.light_preset.clone(),
+                dark_preset: settings.dark_preset.clone(),
+            },
+            profiles: settings.profiles.clone(),
+            keybindings: settings.keybindings.clone(),
+        }
+    }
+}
+
+impl From<ConfigFile> for AppSettings {
+    fn from(file: ConfigFile) -> Self {
+        AppSettings {
+            title_bar_visible: file.window.title_bar_visible,
+            title_template: file.window.title_template,
+            confirm_close: file.window.confirm_close,
+            confirm_close_ignore: file.window.confirm_close_ignore,
+            copy_on_select: file.clipboard.copy_on_select,
+            paste_warning: file.clipboard.paste_warning,
+            bracketed_paste_programs: file.clipboard.bracketed_paste_programs,
+            hyperlinks: file.links.hyperlinks,
+            link_patterns: file.links.patterns,
+            colors: resolve_preset(file.colors),
+            font_family: file.font.family,
+            font_size: file.font.size,
+            light_preset: file.appearance.light_preset,
+            dark_preset: file.appearance.dark_preset,
+            shell: file.shell,
+            default_profile: file.default_profile,
+            profiles: file
+                .profiles
+                .into_iter()
+                .map(|(name, mut profile)| {
+                    profile.colors = profile.colors.map(resolve_preset);
+                    (name, profile)
+                })
+                .collect(),
+            keybindings: file.keybindings,
+        }
+    }
+}
+
+pub fn get_preset_colors(preset: &ColorSchemePreset) -> ColorSettings {
+    let mut settings = match preset {
+        ColorSchemePreset::GruvboxDark => color_schemes::gruvbox_dark::get_colors(),
+        ColorSchemePreset::GruvboxLight => color_schemes::gruvbox_light::get_colors(),
+        ColorSchemePreset::CatppuccinMocha => color_schemes::catppuccin_mocha::get_colors(),
+        ColorSchemePreset::CatppuccinLatte => color_schemes::catppuccin_latte::get_colors(),
+        ColorSchemePreset::Monokai => color_schemes::monokai::get_colors(),
+        ColorSchemePreset::Nord => color_schemes::nord::get_colors(),
+        ColorSchemePreset::TokyoNight => color_schemes::tokyo_night::get_colors(),
+        ColorSchemePreset::TokyoNightDay => color_schemes::tokyo_night_day::get_colors(),
+        ColorSchemePreset::Custom => color_schemes::custom::get_colors(),
+        ColorSchemePreset::User(name) => user_themes::find_user_theme(name)
+            .map(|theme| theme.colors)
+            .unwrap_or_default(),
+    };
     settings.active_preset = Some(preset.name().to_string());
     settings
 }
 
+/// A named preset's colors always come from the built-in scheme or theme
+/// file, so fixes to a preset reach existing configs.
+fn resolve_preset(colors: ColorSettings) -> ColorSettings {
+    match colors.active_preset.as_deref().and_then(ColorSchemePreset::from_name) {
+        Some(preset) if preset != ColorSchemePreset::Custom => {
+            let mut resolved = get_preset_colors(&preset);
+            resolved.background_opacity = colors.background_opacity;
+            resolved
+        }
+        _ => colors,
+    }
+}
+
+/// Replaces a leading `~` with the home directory.
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), dirs::home_dir()) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => format!("{}{}", home.display(), rest),
+        _ => path.to_string(),
+    }
+}
+
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Uses `path` instead of the default config file for the rest of the
+/// process. Only the first call has an effect.
+pub fn set_config_path(path: PathBuf) {
+    let _ = CONFIG_PATH_OVERRIDE.set(path);
+}
+
 pub fn get_config_path() -> Option<PathBuf> {
+    if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+        return Some(path.clone());
+    }
     dirs::home_dir().map(|mut path| {
         path.push(CONFIG_DIR);
         path.push(CONFIG_FILE);
@@ -128,349 +659,536 @@ pub fn get_config_path() -> Option<PathBuf> {
     })
 }
 
-pub fn load_app_settings() -> AppSettings {
-    let mut app_settings = AppSettings::default();
-    
-    let config_path = match get_config_path() {
-        Some(path) if path.exists() => path,
-        _ => return app_settings,
-    };
+pub fn parse_app_settings(contents: &str) -> Result<AppSettings, toml::de::Error> {
+    let file: ConfigFile = toml::from_str(contents)?;
+    if file.version > CONFIG_VERSION {
+        eprintln!(
+            "Config file version {} is newer than supported version {}; unknown settings are ignored.",
+            file.version, CONFIG_VERSION
+        );
+    }
+    Ok(AppSettings::from(file))
+}
 
-    let mut file = match File::open(config_path) {
-        Ok(file) => file,
-        Err(_) => return app_settings,
-    };
+pub fn serialize_app_settings(settings: &AppSettings) -> String {
+    toml::to_string_pretty(&ConfigFile::from(settings))
+        .expect("AppSettings always serializes to TOML")
+}
 
-    let mut contents = String::new();
-    if file.read_to_string(&mut contents).is_err() {
-        return app_settings;
-    }
+/// Files written before the TOML format are flat `key = value` lines without
+/// a `version` key or any table headers.
+pub fn is_legacy_format(contents: &str) -> bool {
+    !contents.lines().map(str::trim).any(|line| {
+        line.starts_with('[') || line.split_once('=').is_some_and(|(key, _)| key.trim() == "version")
+    })
+}
 
+/// Reads the pre-TOML `key = value` format. Only the first `=` separates the
+/// key, so values containing `=` survive.
+pub fn parse_legacy_settings(contents: &str) -> AppSettings {
+    let mut app_settings = AppSettings::default();
     let mut preset_from_config: Option<ColorSchemePreset> = None;
 
-    for line in contents.lines() {
-        let trimmed_line = line.trim();
-        if trimmed_line.is_empty() {
+    for line in contents.replace("\\n", "\n").lines() {
+        let Some((key, value)) = line.split_once('=') else {
             continue;
-        }
+        };
+        let (key, value) = (key.trim(), value.trim());
 
-        let parts: Vec<&str> = trimmed_line.split('=').map(|s| s.trim()).collect();
-        if parts.len() != 2 {
-            continue;
-        }
-
-        match parts[0] {
+        match key {
             "titlebar" => {
-                app_settings.title_bar_visible = parts[1] == "true";
+                app_settings.title_bar_visible = value == "true";
             }
             "font_size" => {
-                if let Ok(size) = parts[1].parse::<f64>() {
+                if let Ok(size) = value.parse::<f64>() {
                     app_settings.font_size = size;
                 }
             }
             "font_family" | "font_name" => {
-                app_settings.font_family = parts[1].to_string();
+                app_settings.font_family = value.to_string();
             }
             "active_preset" => {
-                if let Some(preset) = ColorSchemePreset::from_name(parts[1]) {
+                if let Some(preset) = ColorSchemePreset::from_name(value) {
                     app_settings.colors = get_preset_colors(&preset);
                     preset_from_config = Some(preset);
                 }
-                app_settings.colors.active_preset = Some(parts[1].to_string());
+                app_settings.colors.active_preset = Some(value.to_string());
             }
             "foreground" if preset_from_config.is_none() => {
-                app_settings.colors.foreground = Some(parts[1].to_string());
+                app_settings.colors.foreground = Some(value.to_string());
             }
             "background" if preset_from_config.is_none() => {
-                app_settings.colors.background = Some(parts[1].to_string());
+                app_settings.colors.background = Some(value.to_string());
             }
             "background_opacity" => {
-                if let Ok(opacity) = parts[1].parse::<f64>() {
+                if let Ok(opacity) = value.parse::<f64>() {
                     app_settings.colors.background_opacity = Some(opacity);
                 }
             }
             key if key.starts_with("color") && preset_from_config.is_none() => {
                 if let Ok(index) = key["color".len()..].parse::<usize>() {
                     if index < app_settings.colors.palette.len() {
-                        app_settings.colors.palette[index] = Some(parts[1].to_string());
+                        app_settings.colors.palette[index] = Some(value.to_string());
                     }
                 }
             }
             _ => {}
         }
     }
-    
+
     app_settings
 }
 
-pub fn load_color_settings() -> ColorSettings {
-    let mut settings = ColorSettings::default();
-    
-    let config_path = match get_config_path() {
-        Some(path) if path.exists() => path,
-        _ => return settings,
-    };
+/// Why the config file could not be read.
+#[derive(Debug)]
+pub enum ConfigError {
+    Missing,
+    Io(io::Error),
+    /// The file is there but does not parse, so it must not be written over.
+    Invalid(toml::de::Error),
+}
 
-    let mut file = match File::open(config_path) {
-        Ok(file) => file,
-        Err(_) => return settings,
-    };
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Missing => write!(f, "no config file"),
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Reads and writes the whole config file. Writes go through a temporary
+/// file that is synced and renamed over the original, and keep the user's
+/// comments and any keys this version does not know about.
+pub struct ConfigStore {
+    path: PathBuf,
+}
 
-    let mut contents = String::new();
-    if file.read_to_string(&mut contents).is_err() {
-        return settings;
+impl ConfigStore {
+    pub fn new(path: PathBuf) -> Self {
+        ConfigStore { path }
     }
 
-    let mut preset_from_config: Option<ColorSchemePreset> = None;
-    
-    for line in contents.lines() {
-        let trimmed_line = line.trim();
-        if trimmed_line.is_empty() {
-            continue;
-        }
-        
-        let parts: Vec<&str> = trimmed_line.split('=').map(|s| s.trim()).collect();
-        if parts.len() != 2 {
-            continue;
-        }
+    pub fn open_default() -> Option<Self> {
+        get_config_path().map(ConfigStore::new)
+    }
 
-        match parts[0] {
-            "active_preset" => {
-                if let Some(preset) = ColorSchemePreset::from_name(parts[1]) {
-                    settings = get_preset_colors(&preset);
-                    preset_from_config = Some(preset);
-                }
-                settings.active_preset = Some(parts[1].to_string());
-            }
-            "foreground" if preset_from_config.is_none() => {
-                settings.foreground = Some(parts[1].to_string());
-            }
-            "background" if preset_from_config.is_none() => {
-                settings.background = Some(parts[1].to_string());
-            }
-            "background_opacity" => {
-                if let Ok(opacity) = parts[1].parse::<f64>() {
-                    settings.background_opacity = Some(opacity);
-                }
-            }
-            key if key.starts_with("color") && preset_from_config.is_none() => {
-                if let Ok(index) = key["color".len()..].parse::<usize>() {
-                    if index < settings.palette.len() {
-                        settings.palette[index] = Some(parts[1].to_string());
-                    }
-                }
-            }
-            _ => {}
-        }
+    pub fn path(&self) -> &Path {
+        &self.path
     }
-    
-    settings
-}
 
-pub fn save_title_bar_setting(is_visible: bool) {
-    if let Some(config_path) = get_config_path() {
-        if let Some(parent_dir) = config_path.parent() {
-            if !parent_dir.exists() {
-                if let Err(e) = fs::create_dir_all(parent_dir) {
-                    eprintln!("Failed to create config directory: {}", e);
-                    return;
-                }
-            }
+    /// The settings in the file. An empty file holds the defaults; a
+    /// missing one is an error so callers can tell it from a broken one.
+    pub fn load(&self) -> Result<AppSettings, ConfigError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(ConfigError::Missing),
+            Err(e) => return Err(ConfigError::Io(e)),
+        };
+
+        if contents.trim().is_empty() {
+            return Ok(AppSettings::default());
         }
-        
-        let mut existing_content = String::new();
-        if config_path.exists() {
-            if let Ok(mut file) = File::open(&config_path) {
-                if file.read_to_string(&mut existing_content).is_err() {
-                    eprintln!("Failed to read existing config file, will create/overwrite.");
-                    existing_content.clear();
-                }
-            }
+
+        if is_legacy_format(&contents) {
+            return Ok(self.migrate_legacy(&contents));
         }
 
-        let normalized_content = existing_content.replace("\\n", "\n");
+        parse_app_settings(&contents).map_err(ConfigError::Invalid)
+    }
 
-        let mut output_lines: Vec<String> = normalized_content
-            .lines()
-            .filter(|line_str| {
-                !line_str.trim().starts_with("titlebar =") &&
-                !line_str.trim().starts_with("font_size =")
-            })
-            .map(|s| s.to_string())
-            .collect();
+    pub fn save(&self, settings: &AppSettings) -> io::Result<()> {
+        let existing = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let contents = render_config(&existing, settings)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        write_atomically(&self.path, &contents)
+    }
 
-        output_lines.push(format!("titlebar = {}", is_visible));
+    /// Applies `change` to the settings currently on disk and writes them
+    /// back. Nothing is written while the file does not parse, as that would
+    /// replace everything in it with defaults.
+    pub fn update(&self, change: impl FnOnce(&mut AppSettings)) -> Result<(), ConfigError> {
+        let mut settings = match self.load() {
+            Ok(settings) => settings,
+            Err(ConfigError::Missing) => AppSettings::default(),
+            Err(e) => return Err(e),
+        };
+        change(&mut settings);
+        self.save(&settings).map_err(ConfigError::Io)
+    }
 
-        if let Ok(mut file) = File::create(config_path) {
-            if let Err(e) = file.write_all(output_lines.join("\n").as_bytes()) {
-                eprintln!("Failed to write to config file: {}", e);
-            }
-        } else {
-            eprintln!("Failed to create or open config file for writing.");
+    /// Converts a legacy config in place, keeping the original next to it as
+    /// `better-terminal.conf.bak`.
+    fn migrate_legacy(&self, contents: &str) -> AppSettings {
+        let settings = parse_legacy_settings(contents);
+
+        let backup_path = self.path.with_extension("conf.bak");
+        if let Err(e) = fs::copy(&self.path, &backup_path) {
+            eprintln!("Failed to back up legacy config file, not migrating: {}", e);
+            return settings;
         }
+
+        if let Err(e) = self.save(&settings) {
+            eprintln!("Failed to write migrated config file: {}", e);
+        }
+        settings
     }
 }
 
-pub fn save_font_size_setting(font_size: f64) {
-    if let Some(config_path) = get_config_path() {
-        if let Some(parent_dir) = config_path.parent() {
-            if !parent_dir.exists() {
-                if let Err(e) = fs::create_dir_all(parent_dir) {
-                    eprintln!("Failed to create config directory: {}", e);
-                    return;
-                }
-            }
-        }
+/// Renders `settings` on top of the existing file contents. Values are
+/// replaced in place so comments and formatting around them survive, keys the
+/// schema does not know are left alone, and known keys that are no longer set
+/// are dropped. A legacy or empty file is replaced entirely.
+pub fn render_config(existing: &str, settings: &AppSettings) -> Result<String, toml_edit::TomlError> {
+    let new_document: DocumentMut = serialize_app_settings(settings).parse()?;
+    if existing.trim().is_empty() || is_legacy_format(existing) {
+        return Ok(new_document.to_string());
+    }
 
-        let mut existing_content = String::new();
-        if config_path.exists() {
-            if let Ok(mut file) = File::open(&config_path) {
-                if file.read_to_string(&mut existing_content).is_err() {
-                    eprintln!("Failed to read existing config file, will create/overwrite.");
-                    existing_content.clear();
-                }
-            }
-        }
+    let mut document: DocumentMut = existing.parse()?;
+    // Whatever the schema reads back out of the old file is ours to remove;
+    // everything else belongs to the user.
+    let known_document: DocumentMut = toml::from_str::<ConfigFile>(existing)
+        .ok()
+        .and_then(|file| toml::to_string(&file).ok())
+        .and_then(|contents| contents.parse().ok())
+        .unwrap_or_default();
 
-        let normalized_content = existing_content.replace("\\n", "\n");
+    // Tables are printed in position order, and the positions copied from
+    // `new_document` would interleave new tables with the user's.
+    let mut next_position = last_table_position(document.as_table()) + 1;
+    merge_table(document.as_table_mut(), new_document.as_table(), known_document.as_table(), &mut next_position);
+    Ok(document.to_string())
+}
 
-        let mut output_lines: Vec<String> = normalized_content
-            .lines()
-            .filter(|line_str| !line_str.trim().starts_with("font_size ="))
-            .map(|s| s.to_string())
-            .collect();
+fn last_table_position(table: &toml_edit::Table) -> usize {
+    table
+        .iter()
+        .filter_map(|(_, item)| item.as_table())
+        .map(last_table_position)
+        .chain(table.position())
+        .max()
+        .unwrap_or(0)
+}
 
-        output_lines.push(format!("font_size = {}", font_size));
+fn move_to_end(item: &mut Item, next_position: &mut usize) {
+    if let Some(table) = item.as_table_mut() {
+        table.set_position(*next_position);
+        *next_position += 1;
+        for (_, child) in table.iter_mut() {
+            move_to_end(child, next_position);
+        }
+    }
+}
 
-        if let Ok(mut file) = File::create(config_path) {
-            if let Err(e) = file.write_all(output_lines.join("\n").as_bytes()) {
-                eprintln!("Failed to write to config file: {}", e);
-            }
+fn merge_table(target: &mut dyn TableLike, source: &dyn TableLike, known: &dyn TableLike, next_position: &mut usize) {
+    let stale_keys: Vec<String> = target
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| source.get(key).is_none() && known.get(key).is_some())
+        .collect();
+    for key in stale_keys {
+        target.remove(&key);
+    }
+
+    let empty_table = toml_edit::Table::new();
+    for (key, source_item) in source.iter() {
+        let Some(target_item) = target.get_mut(key) else {
+            let mut new_item = source_item.clone();
+            move_to_end(&mut new_item, next_position);
+            target.insert(key, new_item);
+            continue;
+        };
+
+        if let (Some(target_table), Some(source_table)) = (target_item.as_table_like_mut(), source_item.as_table_like()) {
+            let known_table = known.get(key).and_then(Item::as_table_like).unwrap_or(&empty_table);
+            merge_table(target_table, source_table, known_table, next_position);
+        } else if let (Some(target_value), Some(source_value)) = (target_item.as_value_mut(), source_item.as_value()) {
+            let decor = target_value.decor().clone();
+            *target_value = source_value.clone();
+            *target_value.decor_mut() = decor;
         } else {
-            eprintln!("Failed to create or open config file for writing.");
+            *target_item = source_item.clone();
         }
     }
 }
 
-pub fn save_font_family_setting(font_family: &str) {
-    if let Some(config_path) = get_config_path() {
-        if let Some(parent_dir) = config_path.parent() {
-            if !parent_dir.exists() {
-                if let Err(e) = fs::create_dir_all(parent_dir) {
-                    eprintln!("Failed to create config directory: {}", e);
-                    return;
-                }
-            }
-        }
+/// Writes `contents` to a temporary file next to `path`, syncs it and
+/// renames it into place, so a crash leaves either the old or the new file.
+/// A symlinked file is written through the link, keeping its permissions.
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let parent_dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(parent_dir)?;
 
-        let mut existing_content = String::new();
-        if config_path.exists() {
-            if let Ok(mut file) = File::open(&config_path) {
-                if file.read_to_string(&mut existing_content).is_err() {
-                    eprintln!("Failed to read existing config file, will create/overwrite.");
-                    existing_content.clear();
-                }
-            }
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or(CONFIG_FILE);
+    let temp_path = parent_dir.join(format!(".{}.tmp", file_name));
+    let permissions = fs::metadata(&path).map(|metadata| metadata.permissions()).ok();
+
+    let result = (|| {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(contents.as_bytes())?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
         }
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+        File::open(parent_dir)?.sync_all()
+    })();
 
-        let normalized_content = existing_content.replace("\\n", "\n");
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
 
-        let mut output_lines: Vec<String> = normalized_content
-            .lines()
-            .filter(|line_str| !line_str.trim().starts_with("font_family =") && !line_str.trim().starts_with("font_name ="))
-            .map(|s| s.to_string())
-            .collect();
+pub fn load_app_settings() -> AppSettings {
+    let Some(store) = ConfigStore::open_default() else {
+        return AppSettings::default();
+    };
+    match store.load() {
+        Ok(settings) => settings,
+        Err(ConfigError::Missing) => AppSettings::default(),
+        Err(e) => {
+            eprintln!("Failed to load config file {}: {}", store.path().display(), e);
+            AppSettings::default()
+        }
+    }
+}
 
-        output_lines.push(format!("font_family = {}", font_family));
+/// Like `load_app_settings`, but `None` instead of defaults when the file is
+/// missing or does not parse, so a running window can keep what it has.
+pub fn reload_app_settings() -> Option<AppSettings> {
+    ConfigStore::open_default()?.load().ok()
+}
 
-        if let Ok(mut file) = File::create(config_path) {
-            if let Err(e) = file.write_all(output_lines.join("\n").as_bytes()) {
-                eprintln!("Failed to write to config file: {}", e);
-            }
-        } else {
-            eprintln!("Failed to create or open config file for writing.");
-        }
+pub fn load_color_settings() -> ColorSettings {
+    load_app_settings().colors
+}
+
+pub fn update_app_settings(change: impl FnOnce(&mut AppSettings)) -> Result<(), ConfigError> {
+    match ConfigStore::open_default() {
+        Some(store) => store.update(change),
+        None => Ok(()),
     }
 }
 
-pub fn save_color_settings(settings: &ColorSettings) {
-    if let Some(config_path) = get_config_path() {
-        if let Some(parent_dir) = config_path.parent() {
-            if !parent_dir.exists() {
-                if let Err(e) = fs::create_dir_all(parent_dir) {
-                    eprintln!("Failed to create config directory: {}", e);
-                    return;
-                }
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut existing_content = String::new();
-        if config_path.exists() {
-            if let Ok(mut file) = File::open(&config_path) {
-                if file.read_to_string(&mut existing_content).is_err() {
-                    eprintln!("Failed to read existing config file, will create/overwrite relevant parts.");
-                    existing_content.clear();
-                }
-            }
-        }
-        
-        let normalized_content = existing_content.replace("\\n", "\n");
-
-        let mut new_lines: Vec<String> = normalized_content
-            .lines()
-            .map(|s| s.to_string())
-            .filter(|line_str| {
-                let trimmed_line = line_str.trim();
-                if trimmed_line.starts_with("foreground =") { return false; }
-                if trimmed_line.starts_with("background =") { return false; }
-                if trimmed_line.starts_with("background_opacity =") { return false; }
-                if trimmed_line.starts_with("active_preset =") { return false; }
-                if trimmed_line.starts_with("color") && trimmed_line.contains('=') { return false; }
-                true 
-            })
+    fn temp_config_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("better-terminal-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join(CONFIG_FILE)
+    }
+
+    fn custom_settings() -> AppSettings {
+        let mut settings = AppSettings::default();
+        settings.title_bar_visible = false;
+        settings.font_family = "Fira Code".to_string();
+        settings.font_size = 14.5;
+        settings.colors.foreground = Some("#ebdbb2".to_string());
+        settings.colors.background_opacity = Some(0.9);
+        settings.colors.palette[3] = Some("rgba(249, 226, 175, 1.0)".to_string());
+        settings.hyperlinks = false;
+        settings.link_patterns.push(LinkPattern {
+            pattern: r"JIRA-(\d+)".to_string(),
+            url: "https://jira.example.com/browse/JIRA-$1".to_string(),
+        });
+        settings
+    }
+
+    #[test]
+    fn settings_round_trip_through_toml() {
+        let settings = custom_settings();
+        let parsed = parse_app_settings(&serialize_app_settings(&settings)).unwrap();
+        assert_eq!(parsed, settings);
+    }
+
+    #[test]
+    fn render_keeps_comments_and_unknown_keys() {
+        let existing = "\
+# my terminal config
+version = 1
+
+[window]
+title_bar_visible = true # shown for now
+
+[font]
+family = \"Monospace\"
+size = 12.0
+ligatures = true
+
+[plugins]
+enabled = [\"clock\"]
+";
+        let rendered = render_config(existing, &custom_settings()).unwrap();
+
+        assert!(rendered.contains("# my terminal config"));
+        assert!(rendered.contains("title_bar_visible = false # shown for now"));
+        assert!(rendered.contains("ligatures = true"));
+        assert!(rendered.contains("[plugins]"));
+        assert!(rendered.contains("enabled = [\"clock\"]"));
+        assert_eq!(parse_app_settings(&rendered).unwrap(), custom_settings());
+    }
+
+    #[test]
+    fn render_drops_known_keys_that_are_no_longer_set() {
+        let existing = "version = 1\n\n[colors]\nforeground = \"#ffffff\"\nbackground = \"#000000\"\nnote = \"keep me\"\n";
+        let rendered = render_config(existing, &AppSettings::default()).unwrap();
+
+        assert!(!rendered.contains("foreground"));
+        assert!(!rendered.contains("background ="));
+        assert!(rendered.contains("note = \"keep me\""));
+    }
+
+    #[test]
+    fn profiles_override_global_settings() {
+        let contents = "\
+version = 1
+default_profile = \"prod\"
+
+[font]
+size = 11
+
+[profiles.prod]
+command = \"ssh prod\"
+font_size = 14
+
+[profiles.prod.colors]
+foreground = \"#ff0000\"
+";
+        let settings = parse_app_settings(contents).unwrap();
+        assert_eq!(settings.profile_name(None), Ok(Some("prod".to_string())));
+        assert_eq!(settings.profile_name(Some("missing")), Err("missing".to_string()));
+
+        let prod = settings.with_profile(Some("prod"));
+        assert_eq!(prod.font_size, 14.0);
+        assert_eq!(prod.colors.foreground.as_deref(), Some("#ff0000"));
+        assert_eq!(settings.with_profile(None), settings);
+
+        let rendered = render_config(contents, &settings).unwrap();
+        assert!(rendered.find("[font]").unwrap() < rendered.find("[profiles.prod]").unwrap());
+        assert_eq!(parse_app_settings(&rendered).unwrap(), settings);
+    }
+
+    #[test]
+    fn profile_shell_settings_extend_the_global_ones() {
+        let contents = "\
+version = 1
+
+[shell]
+args = [\"-i\"]
+login = true
+unset_env = [\"TMUX\"]
+
+[shell.env]
+EDITOR = \"vim\"
+
+[profiles.prod]
+command = \"ssh prod\"
+login = false
+exit_behavior = \"hold\"
+
+[profiles.prod.env]
+TERM = \"xterm-256color\"
+";
+        let settings = parse_app_settings(contents).unwrap();
+        assert_eq!(settings.shell_settings(None), settings.shell);
+
+        let prod = settings.shell_settings(Some("prod"));
+        assert_eq!(prod.command.as_deref(), Some("ssh prod"));
+        assert!(prod.args.is_empty());
+        assert!(!prod.login);
+        assert_eq!(prod.exit_behavior, ExitBehavior::Hold);
+        assert_eq!(prod.env.len(), 2);
+        assert_eq!(prod.unset_env, vec!["TMUX".to_string()]);
+        assert_eq!(parse_app_settings(&serialize_app_settings(&settings)).unwrap(), settings);
+    }
+
+    #[test]
+    fn store_save_and_load_round_trip() {
+        let path = temp_config_path("round-trip");
+        let store = ConfigStore::new(path.clone());
+
+        store.save(&custom_settings()).unwrap();
+        assert_eq!(store.load().unwrap(), custom_settings());
+
+        store.update(|settings| settings.font_size = 16.0).unwrap();
+        assert_eq!(store.load().unwrap().font_size, 16.0);
+        assert_eq!(store.load().unwrap().font_family, "Fira Code");
+
+        let leftovers: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
             .collect();
+        assert!(leftovers.is_empty());
 
-        if let Some(preset_name) = &settings.active_preset {
-            new_lines.push(format!("active_preset = {}", preset_name));
-            if ColorSchemePreset::from_name(preset_name).is_none() {
-                if let Some(fg) = &settings.foreground {
-                    new_lines.push(format!("foreground = {}", fg));
-                }
-                if let Some(bg) = &settings.background {
-                    new_lines.push(format!("background = {}", bg));
-                }
-                if let Some(opacity) = &settings.background_opacity {
-                    new_lines.push(format!("background_opacity = {}", opacity));
-                }
-                for (i, color_opt) in settings.palette.iter().enumerate() {
-                    if let Some(color_val) = color_opt {
-                        new_lines.push(format!("color{} = {}", i, color_val));
-                    }
-                }
-            }
-        } else { 
-            if let Some(fg) = &settings.foreground {
-                new_lines.push(format!("foreground = {}", fg));
-            }
-            if let Some(bg) = &settings.background {
-                new_lines.push(format!("background = {}", bg));
-            }
-            if let Some(opacity) = &settings.background_opacity {
-                new_lines.push(format!("background_opacity = {}", opacity));
-            }
-            for (i, color_opt) in settings.palette.iter().enumerate() {
-                if let Some(color_val) = color_opt {
-                    new_lines.push(format!("color{} = {}", i, color_val));
-                }
-            }
-        }
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
 
-        if let Ok(mut file) = File::create(&config_path) {
-            if let Err(e) = file.write_all(new_lines.join("\n").as_bytes()) {
-                eprintln!("Failed to write to config file: {}", e);
-            }
-        } else {
-            eprintln!("Failed to create or open config file for writing.");
-        }
+    #[test]
+    fn store_migrates_legacy_file_once() {
+        let path = temp_config_path("legacy");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "titlebar = false\nfont_family = Fira Code\nfont_size = 14\ncolor3 = #abcdef\n").unwrap();
+        let store = ConfigStore::new(path.clone());
+
+        let settings = store.load().unwrap();
+        assert!(!settings.title_bar_visible);
+        assert_eq!(settings.font_family, "Fira Code");
+        assert_eq!(settings.colors.palette[3].as_deref(), Some("#abcdef"));
+
+        let migrated = fs::read_to_string(&path).unwrap();
+        assert!(!is_legacy_format(&migrated));
+        assert!(path.with_extension("conf.bak").exists());
+        assert_eq!(store.load().unwrap(), settings);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn store_does_not_overwrite_invalid_file() {
+        let path = temp_config_path("invalid");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let contents = "version = 1\n\n[font]\nfamily = \"Fira Code\"\nsize = \"big\"\n";
+        fs::write(&path, contents).unwrap();
+        let store = ConfigStore::new(path.clone());
+
+        assert!(matches!(store.load(), Err(ConfigError::Invalid(_))));
+        assert!(matches!(store.update(|settings| settings.title_bar_visible = false), Err(ConfigError::Invalid(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert!(matches!(ConfigStore::new(path.with_extension("missing")).load(), Err(ConfigError::Missing)));
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn store_writes_through_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let path = temp_config_path("symlink");
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).unwrap();
+        let target = dir.join("dotfiles.conf");
+        fs::write(&target, "").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &path).unwrap();
+        let store = ConfigStore::new(path.clone());
+
+        store.update(|settings| settings.font_size = 16.0).unwrap();
+        assert!(fs::symlink_metadata(&path).unwrap().file_type().is_symlink());
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(store.load().unwrap().font_size, 16.0);
+
+        let _ = fs::remove_dir_all(dir);
     }
 }
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use gtk4::prelude::*;
+use gtk4::Application;
+
+use crate::config::Keybinding;
+
+/// A window action that can be bound to keys from `[keybindings]`.
+pub struct ActionBinding {
+    /// Key in `[keybindings]`.
+    pub name: &'static str,
+    /// Detailed action name, with the target for parameterised actions.
+    pub action: &'static str,
+    pub default_accels: &'static [&'static str],
+}
+
+const fn binding(name: &'static str, action: &'static str, default_accels: &'static [&'static str]) -> ActionBinding {
+    ActionBinding { name, action, default_accels }
+}
+
+pub const ACTION_BINDINGS: &[ActionBinding] = &[
+    binding("copy", "win.copy", &["<Control><Shift>c"]),
+    binding("copy_html", "win.copy_html", &[]),
+    binding("paste", "win.paste", &["<Control><Shift>v"]),
+    binding("paste_selection", "win.paste_selection", &["<Shift>Insert"]),
+    binding("find", "win.find", &["<Control><Shift>f"]),
+    binding("zoom_in", "win.zoom_in", &["<Control>plus", "<Control>equal", "<Control>KP_Add"]),
+    binding("zoom_out", "win.zoom_out", &["<Control>minus", "<Control>KP_Subtract"]),
+    binding("zoom_reset", "win.zoom_reset", &["<Control>0", "<Control>KP_0"]),
+    binding("new_tab", "win.new_tab", &["<Control><Shift>t"]),
+    binding("new_window", "win.new_window", &["<Control><Shift>n"]),
+    binding("close_tab", "win.close_tab", &["<Control><Shift>w"]),
+    binding("split_right", "win.split_right", &["<Control><Shift>e"]),
+    binding("split_down", "win.split_down", &["<Control><Shift>o"]),
+    binding("close_pane", "win.close_pane", &["<Control><Shift>x"]),
+    binding("zoom_pane", "win.zoom_pane", &["<Control><Shift>z"]),
+    binding("focus_pane_left", "win.focus_pane::left", &["<Control><Alt>Left"]),
+    binding("focus_pane_right", "win.focus_pane::right", &["<Control><Alt>Right"]),
+    binding("focus_pane_up", "win.focus_pane::up", &["<Control><Alt>Up"]),
+    binding("focus_pane_down", "win.focus_pane::down", &["<Control><Alt>Down"]),
+    binding("resize_pane_left", "win.resize_pane::left", &["<Control><Alt><Shift>Left"]),
+    binding("resize_pane_right", "win.resize_pane::right", &["<Control><Alt><Shift>Right"]),
+    binding("resize_pane_up", "win.resize_pane::up", &["<Control><Alt><Shift>Up"]),
+    binding("resize_pane_down", "win.resize_pane::down", &["<Control><Alt><Shift>Down"]),
+    binding("toggle_title_bar", "win.toggle_title_bar", &["<Control><Shift>h"]),
+    binding("open_settings", "win.open_settings", &["<Control>comma"]),
+    binding("fullscreen", "win.fullscreen", &["F11"]),
+];
+
+pub fn find_binding(name: &str) -> Option<&'static ActionBinding> {
+    ACTION_BINDINGS.iter().find(|binding| binding.name == name)
+}
+
+/// Registers the accelerators of every action with `app`. Entries in
+/// `keybindings` replace an action's defaults rather than adding to them.
+pub fn apply_keybindings(app: &Application, keybindings: &BTreeMap<String, Keybinding>) {
+    for binding in ACTION_BINDINGS {
+        let accels: Vec<String> = match keybindings.get(binding.name) {
+            Some(keybinding) => keybinding.accels(),
+            None => binding.default_accels.iter().map(|accel| accel.to_string()).collect(),
+        };
+        let accel_refs: Vec<&str> = accels.iter().map(String::as_str).collect();
+        app.set_accels_for_action(binding.action, &accel_refs);
+    }
+}
@@ -0,0 +1,204 @@
+use gtk4::prelude::*;
+use gtk4::{gio, FileLauncher, UriLauncher, Window};
+use vte4::prelude::*;
+use vte4::Terminal;
+
+use std::path::{Path, PathBuf};
+
+use crate::config::LinkPattern;
+use crate::regex::{self, PCRE2_CASELESS, PCRE2_MULTILINE};
+
+const URL_PATTERN: &str = r#"\b(?:https?|ftp)://[^\s<>"'`]+(?<![.,;:!?)\]'"])"#;
+const MAILTO_PATTERN: &str = r"\bmailto:[\w.+-]+@[\w-]+(?:\.[\w-]+)+";
+const FILE_URI_PATTERN: &str = r#"\bfile://[^\s<>"'`]+"#;
+// Absolute paths, paths from `~`, `.` or `..`, and relative paths with at
+// least one slash, each with an optional `:line` or `:line:col`.
+const PATH_PATTERN: &str = r"(?<![\w/.~:@%-])(?:(?:~|\.{1,2})?/[\w.+@-]+(?:/[\w.+@-]+)*/?|[\w.+@-]+(?:/[\w.+@-]+)+/?)(?::\d+(?::\d+)?)?";
+
+/// What a match tag on a terminal stands for.
+#[derive(Debug, Clone)]
+enum LinkKind {
+    Uri,
+    Path,
+    Custom(LinkPattern),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkTarget {
+    Uri(String),
+    File(PathBuf),
+}
+
+impl LinkTarget {
+    /// The text to copy for the link.
+    pub fn address(&self) -> String {
+        match self {
+            LinkTarget::Uri(uri) => uri.clone(),
+            LinkTarget::File(path) => path.to_string_lossy().into_owned(),
+        }
+    }
+}
+
+/// Registers the built-in link patterns and `custom` on `terminal`,
+/// replacing any registered before. VTE underlines a match while the
+/// pointer is over it.
+pub fn add_link_matches(terminal: &Terminal, custom: &[LinkPattern]) {
+    terminal.match_remove_all();
+    let mut kinds = Vec::new();
+    let builtin = [
+        (URL_PATTERN, PCRE2_CASELESS, LinkKind::Uri),
+        (MAILTO_PATTERN, PCRE2_CASELESS, LinkKind::Uri),
+        (FILE_URI_PATTERN, 0, LinkKind::Uri),
+        (PATH_PATTERN, 0, LinkKind::Path),
+    ];
+    let custom = custom.iter().map(|link| (link.pattern.as_str(), 0, LinkKind::Custom(link.clone())));
+    for (pattern, flags, kind) in builtin.into_iter().chain(custom) {
+        let regex = match vte4::Regex::for_match(pattern, flags | PCRE2_MULTILINE) {
+            Ok(regex) => regex,
+            Err(e) => {
+                eprintln!("Invalid link pattern {}: {}", pattern, e);
+                continue;
+            }
+        };
+        let tag = terminal.match_add_regex(&regex, 0);
+        terminal.match_set_cursor_name(tag, "pointer");
+        kinds.push((tag, kind));
+    }
+    // SAFETY: this key only ever holds a `Vec<(i32, LinkKind)>`.
+    unsafe { terminal.set_data(TERMINAL_LINK_KINDS_KEY, kinds) };
+}
+
+const TERMINAL_LINK_KINDS_KEY: &str = "better-terminal-link-kinds";
+
+fn link_kind(terminal: &Terminal, tag: i32) -> Option<LinkKind> {
+    // SAFETY: see `add_link_matches`.
+    unsafe {
+        terminal
+            .data::<Vec<(i32, LinkKind)>>(TERMINAL_LINK_KINDS_KEY)
+            .and_then(|kinds| kinds.as_ref().iter().find(|(kind_tag, _)| *kind_tag == tag).map(|(_, kind)| kind.clone()))
+    }
+}
+
+/// The link under the point `x`, `y` of `terminal`, with relative paths
+/// resolved against `directory`.
+pub fn link_at(terminal: &Terminal, x: f64, y: f64, directory: Option<&str>) -> Option<LinkTarget> {
+    let (text, tag) = terminal.check_match_at(x, y);
+    let text = text?;
+    match link_kind(terminal, tag)? {
+        LinkKind::Uri => Some(LinkTarget::Uri(text.to_string())),
+        LinkKind::Path => resolve_path(&text, directory).map(LinkTarget::File),
+        LinkKind::Custom(link) => expand_url(&link, &text).map(LinkTarget::Uri),
+    }
+}
+
+/// The target of the OSC 8 hyperlink under the point `x`, `y`, as sent by
+/// the program.
+pub fn hyperlink_at(terminal: &Terminal, x: f64, y: f64) -> Option<String> {
+    terminal.check_hyperlink_at(x, y).map(|uri| uri.to_string())
+}
+
+/// What to open for a hyperlink. `ls --hyperlink` and others send
+/// `file://` URIs with our host name in them, which are opened as files.
+pub fn hyperlink_target(uri: &str) -> LinkTarget {
+    if let Ok((path, host)) = glib::filename_from_uri(uri) {
+        if is_local_host(host.as_ref().map(|host| host.as_str())) {
+            return LinkTarget::File(path);
+        }
+    }
+    LinkTarget::Uri(uri.to_string())
+}
+
+pub fn is_local_host(host: Option<&str>) -> bool {
+    host.is_none_or(|host| host == "localhost" || host == glib::host_name())
+}
+
+/// A warning for hyperlinks whose text reads like a URL on another host
+/// than the one `uri` opens, as a link can show any text it likes.
+pub fn host_mismatch(terminal: &Terminal, x: f64, y: f64, uri: &str) -> Option<String> {
+    let (text, tag) = terminal.check_match_at(x, y);
+    let text = text?;
+    if !matches!(link_kind(terminal, tag)?, LinkKind::Uri) {
+        return None;
+    }
+    let shown_host = uri_host(&text)?;
+    match uri_host(uri) {
+        Some(host) if host == shown_host => None,
+        Some(host) => Some(format!("The link text shows {} but the link opens {}.", shown_host, host)),
+        None => Some(format!("The link text shows {} but the link opens {}.", shown_host, uri)),
+    }
+}
+
+/// The host of `uri`, lowercased, for URIs with an authority part.
+fn uri_host(uri: &str) -> Option<String> {
+    let (_, rest) = uri.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// `text` as an existing file, without its `:line:col`. Editors are not
+/// told about the position since the launchers have no way to pass it.
+fn resolve_path(text: &str, directory: Option<&str>) -> Option<PathBuf> {
+    let mut path = text;
+    for _ in 0..2 {
+        if let Some((rest, number)) = path.rsplit_once(':') {
+            if !number.is_empty() && number.bytes().all(|byte| byte.is_ascii_digit()) {
+                path = rest;
+            }
+        }
+    }
+    let path = PathBuf::from(crate::config::expand_home(path));
+    let path = match directory {
+        Some(directory) if path.is_relative() => Path::new(directory).join(path),
+        _ => path,
+    };
+    path.exists().then_some(path)
+}
+
+/// The URL for a match of `link`, with `$0` to `$9` filled in from the
+/// match and its groups.
+fn expand_url(link: &LinkPattern, text: &str) -> Option<String> {
+    let compiled = regex::compile(&link.pattern, false)?;
+    let text = glib::GString::from(text);
+    let match_info = regex::regex_match(&compiled, text.as_gstr())?;
+
+    let mut url = String::new();
+    let mut chars = link.url.chars().peekable();
+    while let Some(c) = chars.next() {
+        let group = chars.peek().and_then(|next| next.to_digit(10));
+        match group {
+            Some(group) if c == '$' => {
+                chars.next();
+                url.push_str(match_info.fetch(group as i32).as_deref().unwrap_or(""));
+            }
+            _ => url.push(c),
+        }
+    }
+    Some(url)
+}
+
+/// Opens `target` with the default handler, reporting failures on stderr.
+pub fn open_link(parent: Option<&Window>, target: &LinkTarget) {
+    match target {
+        LinkTarget::Uri(uri) => {
+            let uri = uri.clone();
+            UriLauncher::new(&uri).launch(parent, None::<&gio::Cancellable>, move |result| {
+                if let Err(e) = result {
+                    eprintln!("Failed to open {}: {}", uri, e);
+                }
+            });
+        }
+        LinkTarget::File(path) => {
+            let path = path.clone();
+            FileLauncher::new(Some(&gio::File::for_path(&path))).launch(parent, None::<&gio::Cancellable>, move |result| {
+                if let Err(e) = result {
+                    eprintln!("Failed to open {}: {}", path.display(), e);
+                }
+            });
+        }
+    }
+}
@@ -1,19 +1,264 @@
 mod config;
 mod ui;
+mod panes;
 mod color_schemes;
+mod validation;
+mod keybindings;
+mod links;
+mod paste;
+mod regex;
+mod search;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 use gtk4::prelude::*;
-use gtk4::Application;
+use gtk4::{gio, Application};
 
 const APP_ID: &str = "com.betterecosystem.terminal";
 
-fn main() {
-    let app = Application::builder().application_id(APP_ID).build();
+fn main() -> glib::ExitCode {
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(gio::ApplicationFlags::HANDLES_COMMAND_LINE | gio::ApplicationFlags::HANDLES_OPEN)
+        .build();
+    add_options(&app);
+
+    app.connect_handle_local_options(handle_local_options);
 
     app.connect_startup(|_| {
         let _ = libadwaita::init();
     });
 
-    app.connect_activate(ui::build_ui);
-    app.run();
+    // Later invocations are forwarded here by the first one, unless started
+    // with --standalone.
+    app.connect_command_line(|app, command_line| {
+        match launch_options(command_line) {
+            Ok(launch_options) => {
+                ui::build_window(app, None, &launch_options);
+                0
+            }
+            Err(e) => {
+                command_line.printerr_literal(&format!("{}\n", e));
+                1
+            }
+        }
+    });
+    // Desktop launchers may activate us over D-Bus without a command line.
+    app.connect_activate(|app| ui::build_window(app, None, &ui::LaunchOptions::default()));
+    // File managers open directories over D-Bus: one window for each.
+    app.connect_open(|app, files, _hint| {
+        for path in files.iter().filter_map(|file| file.path()) {
+            let directory = if path.is_dir() { Some(path.as_path()) } else { path.parent() };
+            let launch_options = ui::LaunchOptions {
+                working_directory: directory.map(|dir| dir.to_string_lossy().into_owned()),
+                ..ui::LaunchOptions::default()
+            };
+            ui::build_window(app, None, &launch_options);
+        }
+    });
+
+    app.run_with_args(&command_args())
+}
+
+fn add_options(app: &Application) {
+    let options: [(&str, u8, glib::OptionArg, &str, Option<&str>); 14] = [
+        ("version", b'v', glib::OptionArg::None, "Print the version and exit", None),
+        ("standalone", 0, glib::OptionArg::None, "Run a separate instance instead of opening a window in the running one", None),
+        ("title", b't', glib::OptionArg::String, "Window title", Some("TITLE")),
+        ("working-directory", b'w', glib::OptionArg::String, "Directory to start in", Some("DIR")),
+        ("command", b'e', glib::OptionArg::String, "Run COMMAND instead of the shell; later arguments are passed to it", Some("COMMAND")),
+        ("config", 0, glib::OptionArg::String, "Read settings from FILE (runs a separate instance)", Some("FILE")),
+        ("class", 0, glib::OptionArg::String, "Window class (runs a separate instance)", Some("CLASS")),
+        ("geometry", 0, glib::OptionArg::String, "Initial size in cells", Some("COLSxROWS")),
+        ("fullscreen", 0, glib::OptionArg::None, "Start in fullscreen", None),
+        ("maximize", 0, glib::OptionArg::None, "Start maximized", None),
+        ("hold", 0, glib::OptionArg::None, "Keep the terminal open after the command exits", None),
+        ("check-config", 0, glib::OptionArg::None, "Check the config file and exit", None),
+        ("import-theme", 0, glib::OptionArg::String, "Import a theme from another terminal and exit", Some("FILE")),
+        // Arguments for the -e command, see `command_args`.
+        ("", 0, glib::OptionArg::StringArray, "", None),
+    ];
+    for (long_name, short_name, arg, description, arg_description) in options {
+        app.add_main_option(long_name, glib::Char::from(short_name), glib::OptionFlags::NONE, arg, description, arg_description);
+    }
+}
+
+fn command_args() -> Vec<String> {
+    end_options_after_command(std::env::args().collect())
+}
+
+/// Everything after `-e COMMAND` belongs to the command, so end option
+/// parsing there: `-e vim -p a b` becomes `-e vim -- -p a b`, and the same
+/// goes for `-evim` and `--command=vim`.
+fn end_options_after_command(mut args: Vec<String>) -> Vec<String> {
+    for index in 1..args.len() {
+        let value_end = match args[index].as_str() {
+            "--" => break,
+            "-e" | "--command" => index + 2,
+            arg if arg.starts_with("--command=") => index + 1,
+            arg if arg.starts_with("-e") => index + 1,
+            _ => continue,
+        };
+        if value_end < args.len() && args[value_end] != "--" {
+            args.insert(value_end, "--".to_string());
+        }
+        break;
+    }
+    args
+}
+
+/// Options that are answered by the process that was started rather than
+/// being sent on to the running instance.
+fn handle_local_options(app: &Application, options: &glib::VariantDict) -> i32 {
+    if options.contains("version") {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return 0;
+    }
+    if let Ok(Some(path)) = options.lookup::<String>("config") {
+        config::set_config_path(absolute_path(&path, None));
+    }
+    if options.contains("check-config") {
+        return check_config().value();
+    }
+    if let Ok(Some(file)) = options.lookup::<String>("import-theme") {
+        return import_theme(&file).value();
+    }
+    if let Ok(Some(class)) = options.lookup::<String>("class") {
+        // Used for WM_CLASS on X11.
+        glib::set_prgname(Some(class.as_str()));
+    }
+
+    // The running instance has its own config file and window class, so
+    // those need a process of their own.
+    if options.contains("standalone") || options.contains("config") || options.contains("class") {
+        app.set_flags(app.flags() | gio::ApplicationFlags::NON_UNIQUE);
+    }
+    -1
+}
+
+/// `path` resolved against `cwd`, or our own directory when the caller's is
+/// unknown.
+fn absolute_path(path: &str, cwd: Option<PathBuf>) -> PathBuf {
+    let path = PathBuf::from(config::expand_home(path));
+    match cwd.or_else(|| std::env::current_dir().ok()) {
+        Some(cwd) => cwd.join(path),
+        None => path,
+    }
+}
+
+/// Reads the options of one invocation, which may come from another process
+/// with its own directory and environment.
+fn launch_options(command_line: &gio::ApplicationCommandLine) -> Result<ui::LaunchOptions, String> {
+    let options = command_line.options_dict();
+    let cwd = command_line.cwd();
+    let string = |name: &str| options.lookup::<String>(name).ok().flatten();
+    let remaining = options.lookup::<Vec<String>>("").ok().flatten().unwrap_or_default();
+
+    let command = match string("command") {
+        Some(command) => Some(std::iter::once(command).chain(remaining).collect()),
+        None if !remaining.is_empty() => return Err(format!("Unexpected argument {}", remaining[0])),
+        None => None,
+    };
+    let geometry = string("geometry").map(|geometry| parse_geometry(&geometry)).transpose()?;
+
+    Ok(ui::LaunchOptions {
+        command,
+        working_directory: string("working-directory")
+            .map(|dir| absolute_path(&dir, cwd.clone()).to_string_lossy().into_owned()),
+        caller_directory: cwd.map(|dir| dir.to_string_lossy().into_owned()),
+        environment: command_line.is_remote().then(|| caller_environment(command_line)),
+        title: string("title"),
+        geometry,
+        fullscreen: options.contains("fullscreen"),
+        maximize: options.contains("maximize"),
+        hold: options.contains("hold"),
+    })
+}
+
+fn caller_environment(command_line: &gio::ApplicationCommandLine) -> BTreeMap<String, String> {
+    command_line
+        .environ()
+        .into_iter()
+        .filter_map(|entry| {
+            let entry = entry.into_string().ok()?;
+            let (name, value) = entry.split_once('=')?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Reads `COLSxROWS`, e.g. `120x40`.
+fn parse_geometry(geometry: &str) -> Result<(i64, i64), String> {
+    let invalid = || format!("Invalid geometry \"{}\", expected COLSxROWS", geometry);
+    let (columns, rows) = geometry.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let columns: i64 = columns.trim().parse().map_err(|_| invalid())?;
+    let rows: i64 = rows.trim().parse().map_err(|_| invalid())?;
+    if columns <= 0 || rows <= 0 {
+        return Err(invalid());
+    }
+    Ok((columns, rows))
+}
+
+fn check_config() -> glib::ExitCode {
+    let diagnostics = validation::check_config();
+    if diagnostics.is_empty() {
+        if let Some(path) = config::get_config_path() {
+            println!("{}: OK", path.display());
+        }
+        return glib::ExitCode::SUCCESS;
+    }
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    glib::ExitCode::FAILURE
+}
+
+fn import_theme(file: &str) -> glib::ExitCode {
+    match color_schemes::import::install_theme_file(std::path::Path::new(file)) {
+        Ok((name, path)) => {
+            println!("Imported \"{}\" to {}", name, path.display());
+            glib::ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            glib::ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn ends_options_after_the_command() {
+        let cases: [(&[&str], &[&str]); 7] = [
+            (&["bt", "-e", "vim", "-p", "a", "b"], &["bt", "-e", "vim", "--", "-p", "a", "b"]),
+            (&["bt", "-evim", "-p", "a", "b"], &["bt", "-evim", "--", "-p", "a", "b"]),
+            (&["bt", "--command", "vim", "-p"], &["bt", "--command", "vim", "--", "-p"]),
+            (&["bt", "--command=vim", "-p"], &["bt", "--command=vim", "--", "-p"]),
+            (&["bt", "-t", "Edit", "-e", "vim"], &["bt", "-t", "Edit", "-e", "vim"]),
+            (&["bt", "-e", "vim", "--", "-p"], &["bt", "-e", "vim", "--", "-p"]),
+            (&["bt", "--", "-e", "vim", "-p"], &["bt", "--", "-e", "vim", "-p"]),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(end_options_after_command(args(input)), args(expected), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_geometry() {
+        assert_eq!(parse_geometry("120x40"), Ok((120, 40)));
+        assert_eq!(parse_geometry("80X24"), Ok((80, 24)));
+        assert_eq!(parse_geometry(" 80 x 24 "), Ok((80, 24)));
+        for invalid in ["0x10", "10x0", "-80x24", "80", "80x", "abc", "axb"] {
+            assert_eq!(parse_geometry(invalid), Err(format!("Invalid geometry \"{}\", expected COLSxROWS", invalid)));
+        }
+    }
 }
@@ -0,0 +1,301 @@
+use gtk4::prelude::*;
+use gtk4::{Box, Orientation, Paned, Widget};
+use vte4::Terminal;
+
+// Every tab owns one root box; splits replace a pane with a Paned holding
+// the old pane and the new one, so the tree is nested Paneds with panes as
+// leaves. A pane is a box around one terminal, with room for widgets that
+// belong to it such as banners.
+const PANE_ROOT_CLASS: &str = "pane-root";
+const PANE_CLASS: &str = "pane";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            "up" => Some(Direction::Up),
+            "down" => Some(Direction::Down),
+            _ => None,
+        }
+    }
+
+    fn orientation(&self) -> Orientation {
+        match self {
+            Direction::Left | Direction::Right => Orientation::Horizontal,
+            Direction::Up | Direction::Down => Orientation::Vertical,
+        }
+    }
+}
+
+/// Wraps `terminal` in a pane. Terminals that are not wrapped are their own
+/// pane.
+pub fn new_pane(terminal: &Terminal) -> Box {
+    let pane = Box::new(Orientation::Vertical, 0);
+    pane.add_css_class(PANE_CLASS);
+    pane.set_hexpand(true);
+    pane.set_vexpand(true);
+    pane.append(terminal);
+    pane
+}
+
+pub fn pane_of(terminal: &Terminal) -> Option<Box> {
+    terminal
+        .parent()
+        .filter(|parent| parent.has_css_class(PANE_CLASS))
+        .and_then(|parent| parent.downcast::<Box>().ok())
+}
+
+fn pane_widget(terminal: &Terminal) -> Widget {
+    match pane_of(terminal) {
+        Some(pane) => pane.upcast(),
+        None => terminal.clone().upcast(),
+    }
+}
+
+pub fn new_pane_root(terminal: &Terminal) -> Box {
+    let root = Box::new(Orientation::Vertical, 0);
+    root.add_css_class(PANE_ROOT_CLASS);
+    root.set_hexpand(true);
+    root.set_vexpand(true);
+    root.append(&pane_widget(terminal));
+    root
+}
+
+pub fn pane_root(widget: &impl IsA<Widget>) -> Option<Box> {
+    let mut current = widget.as_ref().parent();
+    while let Some(widget) = current {
+        if widget.has_css_class(PANE_ROOT_CLASS) {
+            return widget.downcast::<Box>().ok();
+        }
+        current = widget.parent();
+    }
+    None
+}
+
+pub fn terminals_in(widget: &impl IsA<Widget>) -> Vec<Terminal> {
+    let mut terminals = Vec::new();
+    collect_terminals(widget.as_ref(), &mut terminals);
+    terminals
+}
+
+fn collect_terminals(widget: &Widget, terminals: &mut Vec<Terminal>) {
+    if let Some(terminal) = widget.downcast_ref::<Terminal>() {
+        terminals.push(terminal.clone());
+        return;
+    }
+    let mut child = widget.first_child();
+    while let Some(current) = child {
+        collect_terminals(&current, terminals);
+        child = current.next_sibling();
+    }
+}
+
+fn replace_child(parent: &Widget, old: &Widget, new: &Widget) {
+    if let Some(paned) = parent.downcast_ref::<Paned>() {
+        if paned.start_child().as_ref() == Some(old) {
+            paned.set_start_child(Some(new));
+        } else {
+            paned.set_end_child(Some(new));
+        }
+    } else if let Some(container) = parent.downcast_ref::<Box>() {
+        container.remove(old);
+        container.append(new);
+    }
+}
+
+pub fn split(terminal: &Terminal, orientation: Orientation, new_terminal: &Terminal) {
+    let pane = pane_widget(terminal);
+    let Some(parent) = pane.parent() else {
+        return;
+    };
+    if let Some(root) = pane_root(terminal) {
+        unzoom(root.upcast_ref());
+    }
+
+    let half = match orientation {
+        Orientation::Horizontal => terminal.width() / 2,
+        _ => terminal.height() / 2,
+    };
+
+    let paned = Paned::new(orientation);
+    paned.set_hexpand(true);
+    paned.set_vexpand(true);
+    paned.set_shrink_start_child(false);
+    paned.set_shrink_end_child(false);
+
+    replace_child(&parent, &pane, paned.upcast_ref());
+    paned.set_start_child(Some(&pane));
+    paned.set_end_child(Some(&pane_widget(new_terminal)));
+    if half > 0 {
+        paned.set_position(half);
+    }
+
+    new_terminal.grab_focus();
+}
+
+/// Removes `terminal` from its pane tree, letting its sibling take over the
+/// space. Returns `true` when it was the last pane, so the caller can close
+/// the tab.
+pub fn close_pane(terminal: &Terminal) -> bool {
+    let pane = pane_widget(terminal);
+    let Some(parent) = pane.parent() else {
+        return true;
+    };
+    let Some(paned) = parent.downcast_ref::<Paned>() else {
+        return true;
+    };
+
+    let root = pane_root(terminal);
+    let sibling = if paned.start_child().as_ref() == Some(&pane) {
+        paned.end_child()
+    } else {
+        paned.start_child()
+    };
+
+    paned.set_start_child(None::<&Widget>);
+    paned.set_end_child(None::<&Widget>);
+
+    if let (Some(sibling), Some(grandparent)) = (sibling, paned.parent()) {
+        replace_child(&grandparent, paned.upcast_ref(), &sibling);
+        if let Some(root) = root {
+            unzoom(root.upcast_ref());
+        }
+        if let Some(next) = terminals_in(&sibling).first() {
+            next.grab_focus();
+        }
+    }
+    false
+}
+
+pub fn toggle_zoom(terminal: &Terminal) {
+    let Some(root) = pane_root(terminal) else {
+        return;
+    };
+    if is_zoomed(root.upcast_ref()) {
+        unzoom(root.upcast_ref());
+        terminal.grab_focus();
+        return;
+    }
+
+    // Hide every sibling on the way up so each Paned gives its whole area to
+    // the branch holding the zoomed terminal.
+    let mut child = pane_widget(terminal);
+    while let Some(parent) = child.parent() {
+        let Some(paned) = parent.downcast_ref::<Paned>() else {
+            break;
+        };
+        for other in [paned.start_child(), paned.end_child()].into_iter().flatten() {
+            if other != child {
+                other.set_visible(false);
+            }
+        }
+        child = parent;
+    }
+    terminal.grab_focus();
+}
+
+fn is_zoomed(widget: &Widget) -> bool {
+    if let Some(paned) = widget.downcast_ref::<Paned>() {
+        return [paned.start_child(), paned.end_child()]
+            .into_iter()
+            .flatten()
+            .any(|child| !child.is_visible() || is_zoomed(&child));
+    }
+    let mut child = widget.first_child();
+    while let Some(current) = child {
+        if is_zoomed(&current) {
+            return true;
+        }
+        child = current.next_sibling();
+    }
+    false
+}
+
+fn unzoom(widget: &Widget) {
+    if let Some(paned) = widget.downcast_ref::<Paned>() {
+        for child in [paned.start_child(), paned.end_child()].into_iter().flatten() {
+            child.set_visible(true);
+            unzoom(&child);
+        }
+        return;
+    }
+    let mut child = widget.first_child();
+    while let Some(current) = child {
+        unzoom(&current);
+        child = current.next_sibling();
+    }
+}
+
+/// Moves focus to the nearest visible pane in `direction`.
+pub fn focus_neighbor(terminal: &Terminal, direction: Direction) -> bool {
+    let Some(root) = pane_root(terminal) else {
+        return false;
+    };
+    let Some(current) = terminal.compute_bounds(&root) else {
+        return false;
+    };
+    let current_center = (
+        current.x() + current.width() / 2.0,
+        current.y() + current.height() / 2.0,
+    );
+
+    let mut best: Option<(f32, Terminal)> = None;
+    for candidate in terminals_in(&root) {
+        if &candidate == terminal || !candidate.is_mapped() {
+            continue;
+        }
+        let Some(bounds) = candidate.compute_bounds(&root) else {
+            continue;
+        };
+        let in_direction = match direction {
+            Direction::Left => bounds.x() + bounds.width() <= current.x() + 1.0,
+            Direction::Right => bounds.x() >= current.x() + current.width() - 1.0,
+            Direction::Up => bounds.y() + bounds.height() <= current.y() + 1.0,
+            Direction::Down => bounds.y() >= current.y() + current.height() - 1.0,
+        };
+        if !in_direction {
+            continue;
+        }
+        let dx = bounds.x() + bounds.width() / 2.0 - current_center.0;
+        let dy = bounds.y() + bounds.height() / 2.0 - current_center.1;
+        let distance = dx * dx + dy * dy;
+        if best.as_ref().is_none_or(|(best_distance, _)| distance < *best_distance) {
+            best = Some((distance, candidate));
+        }
+    }
+
+    match best {
+        Some((_, neighbor)) => neighbor.grab_focus(),
+        None => false,
+    }
+}
+
+/// Moves the divider of the closest enclosing split that runs along
+/// `direction` by `step` pixels.
+pub fn resize(terminal: &Terminal, direction: Direction, step: i32) {
+    let delta = match direction {
+        Direction::Left | Direction::Up => -step,
+        Direction::Right | Direction::Down => step,
+    };
+    let mut current = terminal.parent();
+    while let Some(widget) = current {
+        if let Some(paned) = widget.downcast_ref::<Paned>() {
+            if paned.orientation() == direction.orientation() {
+                let position = (paned.position() + delta)
+                    .clamp(paned.min_position(), paned.max_position());
+                paned.set_position(position);
+                return;
+            }
+        }
+        current = widget.parent();
+    }
+}
@@ -0,0 +1,102 @@
+// Checks on text about to be pasted into a terminal. Without bracketed
+// paste, a newline in the text runs whatever came before it.
+
+/// Reasons to ask before pasting `text`, in words. Empty when the text
+/// looks harmless. Newlines are not a concern when the program reading the
+/// paste uses bracketed paste, but the commands in the text still are.
+pub fn paste_warnings(text: &str, bracketed: bool) -> Vec<&'static str> {
+    let mut warnings = Vec::new();
+    let newline_runs = !bracketed;
+    if newline_runs && text.trim_end_matches(['\r', '\n']).contains(['\r', '\n']) {
+        warnings.push("The text has several lines, each of which runs as a command.");
+    } else if newline_runs && text.ends_with(['\r', '\n']) {
+        warnings.push("The text ends with a newline, so it runs right away.");
+    }
+    if text.split_whitespace().any(|word| word == "sudo") {
+        warnings.push("The text runs a command with sudo.");
+    }
+    if removes_recursively(text) {
+        warnings.push("The text removes files with rm -rf.");
+    }
+    if text.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t')) {
+        warnings.push("The text contains control characters.");
+    }
+    warnings
+}
+
+/// Whether `text` has an `rm` with both the recursive and the force flag,
+/// however they are spelled.
+fn removes_recursively(text: &str) -> bool {
+    text.lines().any(|line| {
+        let mut words = line.split_whitespace().skip_while(|word| *word != "rm");
+        if words.next().is_none() {
+            return false;
+        }
+        let flags: Vec<&str> = words.take_while(|word| word.starts_with('-')).collect();
+        let has = |short: char, long: &str| {
+            flags
+                .iter()
+                .any(|flag| *flag == long || (!flag.starts_with("--") && flag.contains(short)))
+        };
+        (has('r', "--recursive") || has('R', "--recursive")) && has('f', "--force")
+    })
+}
+
+/// `text` joined into one line without control characters, so pasting it
+/// runs nothing by itself.
+pub fn single_line(text: &str) -> String {
+    let words: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    words
+        .join(" ")
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\t')
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_about_newlines_unless_bracketed() {
+        assert_eq!(paste_warnings("ls -l", false), Vec::<&str>::new());
+        assert_eq!(paste_warnings("ls -l\n", false), ["The text ends with a newline, so it runs right away."]);
+        assert_eq!(
+            paste_warnings("cd /tmp\r\nls\n", false),
+            ["The text has several lines, each of which runs as a command."]
+        );
+        assert!(paste_warnings("cd /tmp\nls\n", true).is_empty());
+    }
+
+    #[test]
+    fn warns_about_sudo_as_a_command_only() {
+        assert_eq!(paste_warnings("sudo apt upgrade", true), ["The text runs a command with sudo."]);
+        assert!(paste_warnings("echo pseudocode sudoku", true).is_empty());
+    }
+
+    #[test]
+    fn finds_recursive_forced_removal() {
+        assert!(removes_recursively("rm -rf build"));
+        assert!(removes_recursively("rm -Rf build"));
+        assert!(removes_recursively("rm -r -f build"));
+        assert!(removes_recursively("rm --recursive --force build"));
+        assert!(removes_recursively("cd /tmp\nsudo rm -fr build"));
+        assert!(!removes_recursively("rm -r build"));
+        assert!(!removes_recursively("rm build -rf"));
+        assert!(!removes_recursively("echo rm; ls -rf"));
+        assert_eq!(paste_warnings("rm -r -f build", true), ["The text removes files with rm -rf."]);
+    }
+
+    #[test]
+    fn warns_about_control_characters() {
+        assert_eq!(paste_warnings("ls\u{1b}[31m", true), ["The text contains control characters."]);
+        assert!(paste_warnings("a\tb", true).is_empty());
+    }
+
+    #[test]
+    fn joins_into_one_line_without_control_characters() {
+        assert_eq!(single_line("cd /tmp\n\n  ls -l\r\n"), "cd /tmp ls -l");
+        assert_eq!(single_line("echo \u{1b}[31mred\u{7}"), "echo [31mred");
+        assert_eq!(single_line("a\tb"), "a\tb");
+    }
+}
@@ -0,0 +1,32 @@
+use glib::translate::from_glib_full;
+use glib::{GStr, MatchInfo, Regex, RegexCompileFlags, RegexMatchFlags};
+
+// Compile flags for VTE's regexes, from pcre2.h. VTE only accepts regexes
+// compiled as multiline.
+pub const PCRE2_CASELESS: u32 = 0x0000_0008;
+pub const PCRE2_MULTILINE: u32 = 0x0000_0400;
+
+/// `pattern` compiled for GLib the way it is compiled for VTE, or `None`
+/// when it is not a valid regex.
+pub fn compile(pattern: &str, caseless: bool) -> Option<Regex> {
+    let mut flags = RegexCompileFlags::MULTILINE;
+    if caseless {
+        flags |= RegexCompileFlags::CASELESS;
+    }
+    Regex::new(pattern, flags, RegexMatchFlags::empty()).ok().flatten()
+}
+
+/// The first match of `regex` in `text`, from which `MatchInfo::next` moves
+/// on to the others. `Regex::match_` is not used as it asserts in debug
+/// builds when nothing matches.
+pub fn regex_match<'input>(regex: &Regex, text: &'input GStr) -> Option<MatchInfo<'input>> {
+    let mut match_info = std::ptr::null_mut();
+    // SAFETY: `text` is nul-terminated and borrowed for as long as the match
+    // info, which takes over the reference GLib always returns through
+    // `match_info`.
+    unsafe {
+        let matched = glib::ffi::g_regex_match(regex.as_ptr(), text.as_ptr(), 0, &mut match_info);
+        let match_info: MatchInfo<'input> = from_glib_full(match_info);
+        (matched != glib::ffi::GFALSE).then_some(match_info)
+    }
+}
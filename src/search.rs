@@ -0,0 +1,263 @@
+use gtk4::prelude::*;
+use gtk4::{Box, Button, Label, Orientation, SearchBar, SearchEntry, ToggleButton};
+use vte4::prelude::*;
+use vte4::Terminal;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::regex::{self, PCRE2_CASELESS, PCRE2_MULTILINE};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+/// The regex for `text`: taken as is in regex mode, escaped otherwise.
+pub fn search_pattern(text: &str, options: SearchOptions) -> String {
+    let pattern = if options.regex {
+        text.to_string()
+    } else {
+        glib::Regex::escape_string(text).to_string()
+    };
+    if options.whole_word {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    }
+}
+
+/// Number of matches of `compiled` in `text`.
+fn count_matches(compiled: &glib::Regex, text: &str) -> usize {
+    let text = glib::GString::from(text);
+    let Some(match_info) = regex::regex_match(compiled, text.as_gstr()) else {
+        return 0;
+    };
+    let mut count = 0;
+    while match_info.matches() {
+        count += 1;
+        if !matches!(match_info.next(), Ok(true)) {
+            break;
+        }
+    }
+    count
+}
+
+/// Everything in `terminal`, scrollback included.
+fn terminal_text(terminal: &Terminal) -> String {
+    let adjustment = terminal.vadjustment();
+    let (first_row, last_row) = match adjustment {
+        Some(adjustment) => (adjustment.lower() as i64, adjustment.upper() as i64),
+        None => (0, terminal.row_count()),
+    };
+    let (text, _length) =
+        terminal.text_range_format(vte4::Format::Text, first_row as _, 0, last_row as _, terminal.column_count() as _);
+    text.map(|text| text.to_string()).unwrap_or_default()
+}
+
+fn clear_search(terminal: &Terminal) {
+    terminal.search_set_regex(None, 0);
+    terminal.unselect_all();
+}
+
+/// Sets the search of `terminal` to `text` and selects the nearest match,
+/// which VTE draws in the theme's selection colors. Returns the pattern
+/// compiled for counting the matches, or `None` when `text` is not a valid
+/// regex.
+fn start_search(terminal: &Terminal, text: &str, options: SearchOptions) -> Option<glib::Regex> {
+    let pattern = search_pattern(text, options);
+    let mut flags = PCRE2_MULTILINE;
+    if !options.case_sensitive {
+        flags |= PCRE2_CASELESS;
+    }
+    let vte_regex = match vte4::Regex::for_search(&pattern, flags) {
+        Ok(vte_regex) => vte_regex,
+        Err(e) => {
+            eprintln!("Invalid search pattern {}: {}", pattern, e);
+            clear_search(terminal);
+            return None;
+        }
+    };
+    let Some(compiled) = regex::compile(&pattern, !options.case_sensitive) else {
+        clear_search(terminal);
+        return None;
+    };
+    terminal.search_set_regex(Some(&vte_regex), 0);
+    terminal.search_set_wrap_around(true);
+    terminal.unselect_all();
+    // The newest output is at the bottom, so search up from there.
+    terminal.search_find_previous();
+    Some(compiled)
+}
+
+/// Shows `summary` next to the entry, which is marked as an error unless
+/// `found`.
+fn show_summary(entry: &SearchEntry, count_label: &Label, summary: &str, found: bool) {
+    if found {
+        entry.remove_css_class("error");
+    } else {
+        entry.add_css_class("error");
+    }
+    count_label.set_text(summary);
+}
+
+fn option_toggle(label: &str, tooltip: &str) -> ToggleButton {
+    let toggle = ToggleButton::with_label(label);
+    toggle.set_tooltip_text(Some(tooltip));
+    toggle.add_css_class("flat");
+    toggle
+}
+
+/// A search bar for the terminal returned by `active_terminal`. Revealing
+/// it is left to the caller; hiding it clears the search and gives focus
+/// back to the terminal.
+pub fn build_search_bar(active_terminal: impl Fn() -> Option<Terminal> + 'static) -> SearchBar {
+    let active_terminal = Rc::new(active_terminal);
+
+    let entry = SearchEntry::new();
+    entry.set_hexpand(true);
+    entry.set_placeholder_text(Some("Find"));
+    let previous_button = Button::from_icon_name("go-up-symbolic");
+    previous_button.set_tooltip_text(Some("Previous Match"));
+    let next_button = Button::from_icon_name("go-down-symbolic");
+    next_button.set_tooltip_text(Some("Next Match"));
+    let regex_toggle = option_toggle(".*", "Regular Expression");
+    let case_toggle = option_toggle("Aa", "Match Case");
+    let whole_word_toggle = option_toggle("W", "Whole Words");
+    let count_label = Label::new(None);
+    count_label.add_css_class("dim-label");
+    count_label.add_css_class("numeric");
+
+    let search_box = Box::new(Orientation::Horizontal, 6);
+    search_box.append(&entry);
+    search_box.append(&count_label);
+    search_box.append(&previous_button);
+    search_box.append(&next_button);
+    search_box.append(&regex_toggle);
+    search_box.append(&case_toggle);
+    search_box.append(&whole_word_toggle);
+
+    let search_bar = SearchBar::new();
+    search_bar.set_child(Some(&search_box));
+    search_bar.connect_entry(&entry);
+    search_bar.set_show_close_button(true);
+
+    let options = Rc::new(Cell::new(SearchOptions::default()));
+
+    let pending_count: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+
+    let update = {
+        let active_terminal = Rc::clone(&active_terminal);
+        let options = Rc::clone(&options);
+        let pending_count = Rc::clone(&pending_count);
+        let entry = entry.clone();
+        let count_label = count_label.clone();
+        Rc::new(move || {
+            if let Some(source) = pending_count.borrow_mut().take() {
+                source.remove();
+            }
+            let Some(terminal) = active_terminal() else { return };
+            let text = entry.text();
+            if text.is_empty() {
+                clear_search(&terminal);
+                show_summary(&entry, &count_label, "", true);
+                return;
+            }
+            let Some(compiled) = start_search(&terminal, &text, options.get()) else {
+                show_summary(&entry, &count_label, "Invalid pattern", false);
+                return;
+            };
+
+            // Counting goes through the whole scrollback, so wait for typing
+            // to pause.
+            let pending_count_clone = Rc::clone(&pending_count);
+            let entry = entry.clone();
+            let count_label = count_label.clone();
+            let source = glib::timeout_add_local_once(Duration::from_millis(150), move || {
+                pending_count_clone.borrow_mut().take();
+                let count = count_matches(&compiled, &terminal_text(&terminal));
+                let summary = match count {
+                    0 => "No matches".to_string(),
+                    1 => "1 match".to_string(),
+                    count => format!("{} matches", count),
+                };
+                show_summary(&entry, &count_label, &summary, count > 0);
+            });
+            *pending_count.borrow_mut() = Some(source);
+        })
+    };
+
+    let update_clone_for_changed = Rc::clone(&update);
+    entry.connect_search_changed(move |_| update_clone_for_changed());
+
+    let toggles: [(&ToggleButton, fn(&mut SearchOptions, bool)); 3] = [
+        (&regex_toggle, |options, active| options.regex = active),
+        (&case_toggle, |options, active| options.case_sensitive = active),
+        (&whole_word_toggle, |options, active| options.whole_word = active),
+    ];
+    for (toggle, set) in toggles {
+        let options_clone_for_toggle = Rc::clone(&options);
+        let update_clone_for_toggle = Rc::clone(&update);
+        toggle.connect_toggled(move |toggle| {
+            let mut current = options_clone_for_toggle.get();
+            set(&mut current, toggle.is_active());
+            options_clone_for_toggle.set(current);
+            update_clone_for_toggle();
+        });
+    }
+
+    let find: Rc<dyn Fn(bool)> = {
+        let active_terminal = Rc::clone(&active_terminal);
+        Rc::new(move |backwards| {
+            if let Some(terminal) = active_terminal() {
+                if backwards {
+                    terminal.search_find_previous();
+                } else {
+                    terminal.search_find_next();
+                }
+            }
+        })
+    };
+
+    // Enter and Ctrl+G go down, Shift+Enter and Ctrl+Shift+G go up.
+    let find_clone_for_next = Rc::clone(&find);
+    next_button.connect_clicked(move |_| find_clone_for_next(false));
+    let find_clone_for_previous = Rc::clone(&find);
+    previous_button.connect_clicked(move |_| find_clone_for_previous(true));
+    let find_clone_for_next_match = Rc::clone(&find);
+    entry.connect_next_match(move |_| find_clone_for_next_match(false));
+    let find_clone_for_previous_match = Rc::clone(&find);
+    entry.connect_previous_match(move |_| find_clone_for_previous_match(true));
+    let find_clone_for_activate = Rc::clone(&find);
+    entry.connect_activate(move |_| find_clone_for_activate(false));
+    let key_controller = gtk4::EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    key_controller.connect_key_pressed(move |_, key, _, state| {
+        let is_enter = key == gtk4::gdk::Key::Return || key == gtk4::gdk::Key::KP_Enter;
+        if is_enter && state.contains(gtk4::gdk::ModifierType::SHIFT_MASK) {
+            find(true);
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    });
+    entry.add_controller(key_controller);
+
+    let active_terminal_clone_for_close = Rc::clone(&active_terminal);
+    search_bar.connect_search_mode_enabled_notify(move |search_bar| {
+        let Some(terminal) = active_terminal_clone_for_close() else { return };
+        if search_bar.is_search_mode() {
+            update();
+        } else {
+            if let Some(source) = pending_count.borrow_mut().take() {
+                source.remove();
+            }
+            terminal.search_set_regex(None, 0);
+            terminal.grab_focus();
+        }
+    });
+
+    search_bar
+}
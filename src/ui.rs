@@ -1,55 +1,152 @@
 use gtk4::prelude::*;
 use vte4::prelude::*;
-use gtk4::{gio, Application, Box, Orientation, PopoverMenu, GestureClick, ColorButton, gdk, DropDown, StringList, FontButton, EventControllerKey}; 
-use libadwaita::{ApplicationWindow, HeaderBar, PreferencesWindow, PreferencesGroup, ActionRow};
+use gtk4::{gio, Application, Box, Orientation, PopoverMenu, GestureClick, ColorButton, gdk, DropDown, StringList, FontButton}; 
+use libadwaita::{ApplicationWindow, Banner, HeaderBar, MessageDialog, PreferencesWindow, PreferencesGroup, ActionRow, ResponseAppearance, TabView, TabBar, WindowTitle};
 use libadwaita::prelude::*;
 
 use vte4::Terminal;
+use std::collections::BTreeMap;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
 
-use crate::config::{save_title_bar_setting, load_color_settings, save_color_settings, ColorSettings, ColorSchemePreset, load_app_settings, get_preset_colors, save_font_family_setting};
+use crate::color_schemes::export::{self, ExportFormat};
+use crate::color_schemes::import;
+use crate::keybindings;
+use crate::links;
+use crate::panes;
+use crate::paste;
+use crate::search;
+use crate::validation::{self, ConfigDiagnostic};
+use crate::config::{expand_home, update_app_settings, ExitBehavior, ShellSettings, load_color_settings, AppSettings, ColorSettings, ColorSchemePreset, load_app_settings, reload_app_settings, get_config_path, get_preset_colors};
 
-pub fn build_ui(app: &Application) {
-    let terminal = Terminal::new();
-    terminal.set_hexpand(true);
-    terminal.set_vexpand(true);
+/// Picks one optional color out of `ColorSettings`, for the settings rows
+/// that edit them.
+type ColorRole = fn(&mut ColorSettings) -> &mut Option<String>;
+
+/// Overrides from the command line for a new window and its first terminal.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions {
+    /// Program and arguments given with `-e`. A single argument is read as a
+    /// command line.
+    pub command: Option<Vec<String>>,
+    pub working_directory: Option<String>,
+    pub title: Option<String>,
+    /// Size of the first terminal in columns and rows.
+    pub geometry: Option<(i64, i64)>,
+    pub fullscreen: bool,
+    pub maximize: bool,
+    /// Keep the first terminal open after its command exits.
+    pub hold: bool,
+    /// Where the window was asked for from. Used when neither the options
+    /// nor the config name a directory.
+    pub caller_directory: Option<String>,
+    /// The full environment of the process that asked for the window, when
+    /// that was not us.
+    pub environment: Option<BTreeMap<String, String>>,
+}
+
+/// Variables VTE sets in the child's environment itself. Passing them on
+/// would override its values.
+const VTE_ENVIRONMENT: [&str; 4] = ["TERM", "COLORTERM", "VTE_VERSION", "PWD"];
+
+impl LaunchOptions {
+    fn apply(&self, shell: &mut ShellSettings) {
+        if let Some(command) = &self.command {
+            let command_line = if command.len() == 1 {
+                command[0].clone()
+            } else {
+                let quoted: Vec<String> = command
+                    .iter()
+                    .map(|arg| glib::shell_quote(arg).to_string_lossy().into_owned())
+                    .collect();
+                quoted.join(" ")
+            };
+            shell.command = Some(command_line);
+            shell.args.clear();
+        }
+        if self.hold {
+            shell.exit_behavior = ExitBehavior::Hold;
+        }
+        if let Some(working_directory) = &self.working_directory {
+            shell.working_directory = Some(working_directory.clone());
+        } else if shell.working_directory.is_none() {
+            shell.working_directory = self.caller_directory.clone();
+        }
+        if let Some(environment) = &self.environment {
+            // The shell gets the caller's environment instead of ours, with
+            // configured variables still winning.
+            let mut env: BTreeMap<String, String> = environment
+                .iter()
+                .filter(|(name, _)| !VTE_ENVIRONMENT.contains(&name.as_str()))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            env.append(&mut shell.env);
+            shell.env = env;
+            shell.replace_environment = true;
+        }
+    }
+}
 
+/// Opens a window whose tabs use `profile`, or the default profile.
+pub fn build_window(app: &Application, profile: Option<&str>, launch_options: &LaunchOptions) {
+    // Check before loading: loading migrates legacy files, which would hide
+    // the problems in them.
+    let config_diagnostics = validation::check_config();
     let app_settings_rc = Rc::new(RefCell::new(load_app_settings()));
-    
-    let initial_colors = app_settings_rc.borrow().colors.clone();
-    let initial_font_family = app_settings_rc.borrow().font_family.clone();
-    let initial_font_size = app_settings_rc.borrow().font_size;
+    let window_profile = match app_settings_rc.borrow().profile_name(profile) {
+        Ok(window_profile) => window_profile,
+        Err(name) => {
+            // An unknown default profile is shown with the other config
+            // problems; only a requested one needs reporting here.
+            if profile.is_some() {
+                eprintln!("Unknown profile \"{}\", using the global settings", name);
+            }
+            None
+        }
+    };
+
     let initial_title_bar_visible = app_settings_rc.borrow().title_bar_visible;
 
-    apply_color_settings(&terminal, &initial_colors);
-    let font_desc = pango::FontDescription::from_string(&format!("{} {}", initial_font_family, initial_font_size));
-    terminal.set_font(Some(&font_desc));
+    let tab_view = TabView::new();
+    tab_view.set_hexpand(true);
+    tab_view.set_vexpand(true);
+
+    let tab_bar = TabBar::new();
+    tab_bar.set_view(Some(&tab_view));
+    tab_bar.set_hexpand(true);
+
+    // The tab bar hides itself while there is only one tab; the window
+    // title takes its place then.
+    let window_title = WindowTitle::new("Better Terminal", "");
+    window_title.set_hexpand(true);
+    let title_box = Box::new(Orientation::Horizontal, 0);
+    title_box.append(&window_title);
+    title_box.append(&tab_bar);
+    let window_title_clone_for_tabs = window_title.clone();
+    tab_bar.connect_tabs_revealed_notify(move |tab_bar| {
+        let revealed = tab_bar.is_tabs_revealed();
+        tab_bar.set_visible(revealed);
+        window_title_clone_for_tabs.set_visible(!revealed);
+    });
+    tab_bar.set_visible(tab_bar.is_tabs_revealed());
+    window_title.set_visible(!tab_bar.is_tabs_revealed());
 
-    let default_shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
-    terminal.spawn_async(
-        vte4::PtyFlags::DEFAULT,
-        None,
-        &[&default_shell],
-        &[],
-        glib::SpawnFlags::DEFAULT,
-        || {},
-        -1,
-        None::<&gio::Cancellable>,
-        |result| {
-            if let Err(e) = result {
-                eprintln!("Failed to spawn shell: {}", e);
-            }
-        },
-    );
     let header_bar = HeaderBar::new();
     header_bar.set_show_end_title_buttons(true);
+    header_bar.set_title_widget(Some(&title_box));
 
     header_bar.set_visible(initial_title_bar_visible);
 
     let content_box = Box::new(Orientation::Vertical, 0);
     content_box.append(&header_bar);
-    content_box.append(&terminal);
+    let config_banner = Banner::new("");
+    config_banner.set_use_markup(false);
+    config_banner.set_button_label(Some("Dismiss"));
+    config_banner.connect_button_clicked(|banner| banner.set_revealed(false));
+    update_config_banner(&config_banner, &config_diagnostics);
+    content_box.append(&config_banner);
+    content_box.append(&tab_view);
 
     let window = ApplicationWindow::builder()
         .application(app)
@@ -59,22 +156,24 @@ pub fn build_ui(app: &Application) {
         .content(&content_box)
         .build();
 
-    let menu = gio::Menu::new();
-    menu.append(Some("Show Title Bar"), Some("win.toggle_title_bar"));
-    menu.append(Some("Settings"), Some("win.open_settings")); 
-
-    let popover = PopoverMenu::from_model(Some(&menu));
-    popover.set_parent(&terminal);
+    let window_weak_for_search = window.downgrade();
+    let tab_view_clone_for_search = tab_view.clone();
+    let search_bar = search::build_search_bar(move || {
+        let window = window_weak_for_search.upgrade()?;
+        active_terminal(&window, &tab_view_clone_for_search)
+    });
+    content_box.insert_child_after(&search_bar, Some(&config_banner));
+    let find_action = gio::SimpleAction::new("find", None);
+    find_action.connect_activate(move |_, _| search_bar.set_search_mode(!search_bar.is_search_mode()));
+    window.add_action(&find_action);
 
-    let gesture = GestureClick::new();
-    gesture.set_button(3);
-    let popover_clone = popover.clone();
-    gesture.connect_pressed(move |_, _, x, y| {
-        let rect = gtk4::gdk::Rectangle::new(x as i32, y as i32, 1, 1);
-        popover_clone.set_pointing_to(Some(&rect));
-        popover_clone.popup();
+    // Settings that cannot be saved leave the file alone and show why here.
+    let show_config_problems_action = gio::SimpleAction::new("show_config_problems", None);
+    let config_banner_clone_for_problems = config_banner.clone();
+    show_config_problems_action.connect_activate(move |_, _| {
+        update_config_banner(&config_banner_clone_for_problems, &validation::check_config());
     });
-    terminal.add_controller(gesture);
+    window.add_action(&show_config_problems_action);
 
     let header_bar_clone = header_bar.clone();
     let toggle_title_bar_action = gio::SimpleAction::new_stateful(
@@ -91,72 +190,1184 @@ pub fn build_ui(app: &Application) {
         let new_state = !current_state;
         header_bar_clone.set_visible(new_state);
         action.set_state(&glib::Variant::from(new_state));
-        save_title_bar_setting(new_state);
+        save_app_settings(&header_bar_clone, |settings| settings.title_bar_visible = new_state);
     });
     window.add_action(&toggle_title_bar_action);
+
+    // Terminals activate this when anything the title shows has changed.
+    let update_title_action = gio::SimpleAction::new("update_title", None);
+    let window_weak_for_title = window.downgrade();
+    let tab_view_clone_for_title = tab_view.clone();
+    let app_settings_for_title = Rc::clone(&app_settings_rc);
+    update_title_action.connect_activate(move |_, _| {
+        let Some(window) = window_weak_for_title.upgrade() else { return };
+        let title = window_title_text(&window, &tab_view_clone_for_title, &app_settings_for_title.borrow().title_template);
+        window.set_title(Some(&title));
+        window_title.set_title(&title);
+    });
+    window.add_action(&update_title_action);
+    let update_title_action_clone = update_title_action.clone();
+    tab_view.connect_selected_page_notify(move |_| update_title_action_clone.activate(None));
+
+    let set_title_action = gio::SimpleAction::new("set_title", None);
+    let window_weak_for_set_title = window.downgrade();
+    let tab_view_clone_for_set_title = tab_view.clone();
+    set_title_action.connect_activate(move |_, _| {
+        let Some(window) = window_weak_for_set_title.upgrade() else { return };
+        if let Some(page) = tab_view_clone_for_set_title.selected_page() {
+            show_set_title_dialog(&window, &tab_view_clone_for_set_title, &page);
+        }
+    });
+    window.add_action(&set_title_action);
+
+    // Set once the user has agreed to close despite running programs.
+    let close_confirmed = Rc::new(Cell::new(false));
+    let tab_view_clone_for_close = tab_view.clone();
+    let app_settings_for_close = Rc::clone(&app_settings_rc);
+    window.connect_close_request(move |window| {
+        let app_settings = app_settings_for_close.borrow();
+        if close_confirmed.get() || !app_settings.confirm_close {
+            return glib::Propagation::Proceed;
+        }
+        let running = running_programs(&tab_view_clone_for_close, &app_settings.confirm_close_ignore);
+        if running.is_empty() {
+            return glib::Propagation::Proceed;
+        }
+        confirm_close_window(window, &running, &close_confirmed);
+        glib::Propagation::Stop
+    });
+
+    watch_config(&window, &tab_view, &header_bar, &toggle_title_bar_action, &config_banner, &app_settings_rc);
+    follow_system_style(&window, &tab_view, &app_settings_rc);
     
     let open_settings_action = gio::SimpleAction::new("open_settings", None);
     let window_clone_for_settings = window.clone();
-    let terminal_clone_for_settings = terminal.clone();
-    let window_for_settings = window.clone();
+    let tab_view_clone_for_settings = tab_view.clone();
+    let app_settings_for_settings = Rc::clone(&app_settings_rc);
     open_settings_action.connect_activate(move |_, _| {
-        build_settings_window(&window_clone_for_settings, &terminal_clone_for_settings, &window_for_settings);
+        build_settings_window(&window_clone_for_settings, &tab_view_clone_for_settings, &app_settings_for_settings);
     });
     window.add_action(&open_settings_action);
 
-    let window_clone = window.clone();
-    terminal.connect_child_exited(move |_terminal, _status| {
-        window_clone.close();
-    });
-
-    // KEY CONTROLLER FOR FONT SIZE
-    let key_controller = EventControllerKey::new();
-    let terminal_clone_for_keys = terminal.clone();
-    let app_settings_for_keys = Rc::clone(&app_settings_rc);
-
-    key_controller.connect_key_pressed(move |_, keyval, _, modifier| {
-        if modifier.contains(gdk::ModifierType::CONTROL_MASK) {
-            let mut app_settings_borrowed = app_settings_for_keys.borrow_mut();
-            let mut new_font_size = app_settings_borrowed.font_size;
-            let mut changed = false;
-
-            const MIN_FONT_SIZE: f64 = 6.0;
-            const MAX_FONT_SIZE: f64 = 72.0;
-            const FONT_STEP: f64 = 1.0;
-
-            match keyval {
-                gdk::Key::plus | gdk::Key::equal | gdk::Key::KP_Add => {
-                    if new_font_size < MAX_FONT_SIZE {
-                        new_font_size = (new_font_size + FONT_STEP).min(MAX_FONT_SIZE);
-                        changed = true;
-                    }
+    let new_tab_action = gio::SimpleAction::new("new_tab", None);
+    let tab_view_clone_for_new_tab = tab_view.clone();
+    let app_settings_for_new_tab = Rc::clone(&app_settings_rc);
+    let window_profile_for_new_tab = window_profile.clone();
+    new_tab_action.connect_activate(move |_, _| {
+        add_tab(&tab_view_clone_for_new_tab, &app_settings_for_new_tab.borrow(), window_profile_for_new_tab.as_deref());
+    });
+    window.add_action(&new_tab_action);
+
+    let new_tab_with_profile_action = gio::SimpleAction::new("new_tab_with_profile", Some(glib::VariantTy::STRING));
+    let tab_view_clone_for_profile_tab = tab_view.clone();
+    let app_settings_for_profile_tab = Rc::clone(&app_settings_rc);
+    new_tab_with_profile_action.connect_activate(move |_, parameter| {
+        let Some(name) = parameter.and_then(|p| p.get::<String>()) else { return };
+        let app_settings = app_settings_for_profile_tab.borrow();
+        // The menu only offers existing profiles; a miss means the config
+        // has changed since, and the global settings are used.
+        let profile = app_settings.profile_name(Some(&name)).unwrap_or(None);
+        add_tab(&tab_view_clone_for_profile_tab, &app_settings, profile.as_deref());
+    });
+    window.add_action(&new_tab_with_profile_action);
+
+    let new_window_action = gio::SimpleAction::new("new_window", None);
+    let app_weak_for_new_window = app.downgrade();
+    let window_weak_for_new_window = window.downgrade();
+    let tab_view_clone_for_new_window = tab_view.clone();
+    new_window_action.connect_activate(move |_, _| {
+        let Some(app) = app_weak_for_new_window.upgrade() else { return };
+        let launch_options = window_weak_for_new_window
+            .upgrade()
+            .map(|window| inherited_launch_options(&window, &tab_view_clone_for_new_window))
+            .unwrap_or_default();
+        build_window(&app, None, &launch_options);
+    });
+    window.add_action(&new_window_action);
+
+    let new_window_with_profile_action = gio::SimpleAction::new("new_window_with_profile", Some(glib::VariantTy::STRING));
+    let app_weak_for_profile_window = app.downgrade();
+    let window_weak_for_profile_window = window.downgrade();
+    let tab_view_clone_for_profile_window = tab_view.clone();
+    new_window_with_profile_action.connect_activate(move |_, parameter| {
+        let Some(name) = parameter.and_then(|p| p.get::<String>()) else { return };
+        let Some(app) = app_weak_for_profile_window.upgrade() else { return };
+        let launch_options = window_weak_for_profile_window
+            .upgrade()
+            .map(|window| inherited_launch_options(&window, &tab_view_clone_for_profile_window))
+            .unwrap_or_default();
+        build_window(&app, Some(&name), &launch_options);
+    });
+    window.add_action(&new_window_with_profile_action);
+
+    let close_tab_action = gio::SimpleAction::new("close_tab", None);
+    let tab_view_clone_for_close_tab = tab_view.clone();
+    close_tab_action.connect_activate(move |_, _| {
+        if let Some(page) = tab_view_clone_for_close_tab.selected_page() {
+            tab_view_clone_for_close_tab.close_page(&page);
+        }
+    });
+    window.add_action(&close_tab_action);
+
+    for (name, orientation) in [("split_right", Orientation::Horizontal), ("split_down", Orientation::Vertical)] {
+        let split_action = gio::SimpleAction::new(name, None);
+        let window_clone_for_split = window.clone();
+        let tab_view_clone_for_split = tab_view.clone();
+        let app_settings_for_split = Rc::clone(&app_settings_rc);
+        split_action.connect_activate(move |_, _| {
+            if let Some(terminal) = active_terminal(&window_clone_for_split, &tab_view_clone_for_split) {
+                split_pane(&tab_view_clone_for_split, &terminal, orientation, &app_settings_for_split.borrow());
+            }
+        });
+        window.add_action(&split_action);
+    }
+
+    let close_pane_action = gio::SimpleAction::new("close_pane", None);
+    let window_clone_for_close_pane = window.clone();
+    let tab_view_clone_for_close_pane = tab_view.clone();
+    close_pane_action.connect_activate(move |_, _| {
+        let Some(terminal) = active_terminal(&window_clone_for_close_pane, &tab_view_clone_for_close_pane) else { return };
+        let Some(root) = panes::pane_root(&terminal) else { return };
+        if panes::close_pane(&terminal) {
+            tab_view_clone_for_close_pane.close_page(&tab_view_clone_for_close_pane.page(&root));
+        }
+        let _ = tab_view_clone_for_close_pane.activate_action("win.update_pane_actions", None);
+    });
+    window.add_action(&close_pane_action);
+
+    let zoom_pane_action = gio::SimpleAction::new("zoom_pane", None);
+    let window_clone_for_zoom = window.clone();
+    let tab_view_clone_for_zoom = tab_view.clone();
+    zoom_pane_action.connect_activate(move |_, _| {
+        if let Some(terminal) = active_terminal(&window_clone_for_zoom, &tab_view_clone_for_zoom) {
+            panes::toggle_zoom(&terminal);
+        }
+    });
+    window.add_action(&zoom_pane_action);
+
+    let focus_pane_action = gio::SimpleAction::new("focus_pane", Some(glib::VariantTy::STRING));
+    let window_clone_for_focus = window.clone();
+    let tab_view_clone_for_focus = tab_view.clone();
+    focus_pane_action.connect_activate(move |_, parameter| {
+        let Some(direction) = parameter
+            .and_then(|p| p.get::<String>())
+            .and_then(|name| panes::Direction::from_name(&name)) else { return };
+        if let Some(terminal) = active_terminal(&window_clone_for_focus, &tab_view_clone_for_focus) {
+            panes::focus_neighbor(&terminal, direction);
+        }
+    });
+    window.add_action(&focus_pane_action);
+
+    let resize_pane_action = gio::SimpleAction::new("resize_pane", Some(glib::VariantTy::STRING));
+    let window_clone_for_resize = window.clone();
+    let tab_view_clone_for_resize = tab_view.clone();
+    resize_pane_action.connect_activate(move |_, parameter| {
+        const RESIZE_STEP: i32 = 20;
+        let Some(direction) = parameter
+            .and_then(|p| p.get::<String>())
+            .and_then(|name| panes::Direction::from_name(&name)) else { return };
+        if let Some(terminal) = active_terminal(&window_clone_for_resize, &tab_view_clone_for_resize) {
+            panes::resize(&terminal, direction, RESIZE_STEP);
+        }
+    });
+    window.add_action(&resize_pane_action);
+
+    // Moving between or resizing panes only makes sense with more than one,
+    // and with one the keys belong to the terminal.
+    let update_pane_actions_action = gio::SimpleAction::new("update_pane_actions", None);
+    let tab_view_clone_for_pane_actions = tab_view.clone();
+    update_pane_actions_action.connect_activate(move |_, _| {
+        let split = tab_view_clone_for_pane_actions
+            .selected_page()
+            .is_some_and(|page| panes::terminals_in(&page.child()).len() > 1);
+        focus_pane_action.set_enabled(split);
+        resize_pane_action.set_enabled(split);
+    });
+    window.add_action(&update_pane_actions_action);
+    let update_pane_actions_clone = update_pane_actions_action.clone();
+    tab_view.connect_selected_page_notify(move |_| update_pane_actions_clone.activate(None));
+    update_pane_actions_action.activate(None);
+
+    for (name, format) in [("copy", vte4::Format::Text), ("copy_html", vte4::Format::Html)] {
+        let copy_action = gio::SimpleAction::new(name, None);
+        let window_clone_for_copy = window.clone();
+        let tab_view_clone_for_copy = tab_view.clone();
+        copy_action.connect_activate(move |_, _| {
+            if let Some(terminal) = active_terminal(&window_clone_for_copy, &tab_view_clone_for_copy) {
+                terminal.copy_clipboard_format(format);
+            }
+        });
+        window.add_action(&copy_action);
+    }
+
+    for (name, primary) in [("paste", false), ("paste_selection", true)] {
+        let paste_action = gio::SimpleAction::new(name, None);
+        let window_clone_for_paste = window.clone();
+        let tab_view_clone_for_paste = tab_view.clone();
+        let app_settings_for_paste = Rc::clone(&app_settings_rc);
+        paste_action.connect_activate(move |_, _| {
+            let Some(terminal) = active_terminal(&window_clone_for_paste, &tab_view_clone_for_paste) else { return };
+            let app_settings = app_settings_for_paste.borrow();
+            if !app_settings.paste_warning {
+                if primary {
+                    terminal.paste_primary();
+                } else {
+                    terminal.paste_clipboard();
                 }
-                gdk::Key::minus | gdk::Key::KP_Subtract => {
-                    if new_font_size > MIN_FONT_SIZE {
-                        new_font_size = (new_font_size - FONT_STEP).max(MIN_FONT_SIZE);
-                        changed = true;
+                return;
+            }
+
+            let bracketed = foreground_process(&terminal)
+                .is_some_and(|(_, name)| app_settings.bracketed_paste_programs.contains(&name));
+            let clipboard = if primary { terminal.primary_clipboard() } else { terminal.clipboard() };
+            let window_weak = window_clone_for_paste.downgrade();
+            clipboard.read_text_async(None::<&gio::Cancellable>, move |result| {
+                let text = match result {
+                    Ok(Some(text)) => text,
+                    Ok(None) => return,
+                    Err(e) => {
+                        eprintln!("Failed to read clipboard: {}", e);
+                        return;
                     }
+                };
+                let warnings = paste::paste_warnings(&text, bracketed);
+                match window_weak.upgrade() {
+                    Some(window) if !warnings.is_empty() => confirm_paste(&window, &terminal, &text, &warnings),
+                    _ => terminal.paste_text(&text),
                 }
-                _ => return glib::Propagation::Proceed,
+            });
+        });
+        window.add_action(&paste_action);
+    }
+
+    // Zoom belongs to this window's terminals and is not saved. Each one
+    // zooms from its own profile's font size.
+    const FONT_STEP: f64 = 1.0;
+    let zoom_actions: [(&str, fn(f64) -> f64); 3] = [
+        ("zoom_in", |zoom| zoom + FONT_STEP),
+        ("zoom_out", |zoom| zoom - FONT_STEP),
+        ("zoom_reset", |_| 0.0),
+    ];
+    for (name, change_zoom) in zoom_actions {
+        let zoom_action = gio::SimpleAction::new(name, None);
+        let tab_view_clone_for_zoom = tab_view.clone();
+        let app_settings_for_zoom = Rc::clone(&app_settings_rc);
+        zoom_action.connect_activate(move |_, _| {
+            let app_settings = app_settings_for_zoom.borrow();
+            for terminal in tab_view_terminals(&tab_view_clone_for_zoom) {
+                let base_size = app_settings.with_profile(terminal_profile(&terminal).as_deref()).font_size;
+                let font_size = (base_size + change_zoom(terminal_zoom(&terminal))).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+                set_terminal_zoom(&terminal, font_size - base_size);
+                apply_font_to_terminal(&terminal, &app_settings);
             }
+        });
+        window.add_action(&zoom_action);
+    }
 
-            if changed {
-                app_settings_borrowed.font_size = new_font_size;
-                let font_desc = pango::FontDescription::from_string(
-                    &format!("{} {}", app_settings_borrowed.font_family, new_font_size)
-                );
-                terminal_clone_for_keys.set_font(Some(&font_desc));
-                crate::config::save_font_size_setting(new_font_size);
-                return glib::Propagation::Stop; 
+    let fullscreen_action = gio::SimpleAction::new("fullscreen", None);
+    let window_weak_for_fullscreen = window.downgrade();
+    fullscreen_action.connect_activate(move |_, _| {
+        let Some(window) = window_weak_for_fullscreen.upgrade() else { return };
+        window.set_fullscreened(!window.is_fullscreen());
+    });
+    window.add_action(&fullscreen_action);
+
+    keybindings::apply_keybindings(app, &app_settings_rc.borrow().keybindings);
+
+    // The window lives as long as it has tabs; closing the last one closes it.
+    let window_clone = window.clone();
+    tab_view.connect_n_pages_notify(move |tab_view| {
+        if tab_view.n_pages() == 0 {
+            window_clone.close();
+        }
+    });
+
+    // Keep a terminal focused when switching tabs.
+    tab_view.connect_selected_page_notify(|tab_view| {
+        if let Some(page) = tab_view.selected_page() {
+            if let Some(terminal) = panes::terminals_in(&page.child()).first() {
+                terminal.grab_focus();
             }
         }
-        glib::Propagation::Proceed
     });
-    terminal.add_controller(key_controller);
+
+
+    let mut first_shell = app_settings_rc.borrow().shell_settings(window_profile.as_deref());
+    launch_options.apply(&mut first_shell);
+    let first_terminal = add_tab_with_shell(&tab_view, &app_settings_rc.borrow(), window_profile.as_deref(), &first_shell);
+
+    if let Some(title) = &launch_options.title {
+        if let Some(root) = panes::pane_root(&first_terminal) {
+            pin_tab_title(&tab_view, &root, Some(title));
+        }
+    }
+    if let Some((columns, rows)) = launch_options.geometry {
+        // The window then takes its natural size, which follows the
+        // terminal's grid.
+        first_terminal.set_size(columns, rows);
+        window.set_default_size(-1, -1);
+    }
+    if launch_options.fullscreen {
+        window.fullscreen();
+    } else if launch_options.maximize {
+        window.maximize();
+    }
 
     window.present();
 }
 
+/// Writes `change` to the config file. A file that does not parse is left
+/// alone, and the window of `widget` shows what is wrong with it instead.
+fn save_app_settings(widget: &impl IsA<gtk4::Widget>, change: impl FnOnce(&mut AppSettings)) {
+    if let Err(e) = update_app_settings(change) {
+        eprintln!("Not saving settings: {}", e);
+        let _ = widget.activate_action("win.show_config_problems", None);
+    }
+}
+
+fn update_config_banner(banner: &Banner, diagnostics: &[ConfigDiagnostic]) {
+    if let Some(first) = diagnostics.first() {
+        let title = match diagnostics.len() {
+            1 => format!("Config problem: {}", first),
+            n => format!("Config problem: {} (and {} more)", first, n - 1),
+        };
+        banner.set_title(&title);
+        let details: Vec<String> = diagnostics.iter().map(ToString::to_string).collect();
+        banner.set_tooltip_text(Some(&details.join("\n")));
+        banner.set_revealed(true);
+    } else {
+        banner.set_revealed(false);
+    }
+}
+
+// Re-reads the config whenever it changes on disk and applies it to this
+// window without restarting any shells.
+fn watch_config(
+    window: &ApplicationWindow,
+    tab_view: &TabView,
+    header_bar: &HeaderBar,
+    toggle_title_bar_action: &gio::SimpleAction,
+    banner: &Banner,
+    app_settings_rc: &Rc<RefCell<AppSettings>>,
+) {
+    let Some(config_path) = get_config_path() else { return };
+    let monitor = match gio::File::for_path(&config_path).monitor_file(gio::FileMonitorFlags::WATCH_MOVES, None::<&gio::Cancellable>) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            eprintln!("Failed to watch config file: {}", e);
+            return;
+        }
+    };
+
+    let pending_reload: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
+    let window_weak = window.downgrade();
+    let tab_view_weak = tab_view.downgrade();
+    let header_bar_weak = header_bar.downgrade();
+    let banner_weak = banner.downgrade();
+    let toggle_title_bar_action = toggle_title_bar_action.clone();
+    let app_settings_for_reload = Rc::clone(app_settings_rc);
+    monitor.connect_changed(move |_, _, _, event| {
+        if matches!(
+            event,
+            gio::FileMonitorEvent::Deleted
+                | gio::FileMonitorEvent::AttributeChanged
+                | gio::FileMonitorEvent::PreUnmount
+                | gio::FileMonitorEvent::Unmounted
+        ) {
+            return;
+        }
+
+        // Editors save in several steps; reload once they have settled.
+        if let Some(source) = pending_reload.borrow_mut().take() {
+            source.remove();
+        }
+        let pending_reload_clone = Rc::clone(&pending_reload);
+        let window_weak = window_weak.clone();
+        let tab_view_weak = tab_view_weak.clone();
+        let header_bar_weak = header_bar_weak.clone();
+        let banner_weak = banner_weak.clone();
+        let toggle_title_bar_action = toggle_title_bar_action.clone();
+        let app_settings_for_reload = Rc::clone(&app_settings_for_reload);
+        let source = glib::timeout_add_local_once(Duration::from_millis(200), move || {
+            pending_reload_clone.borrow_mut().take();
+            let (Some(tab_view), Some(header_bar), Some(banner)) =
+                (tab_view_weak.upgrade(), header_bar_weak.upgrade(), banner_weak.upgrade()) else { return };
+            update_config_banner(&banner, &validation::check_config());
+
+            // Keep the current settings while the file does not parse, e.g.
+            // halfway through an edit.
+            let Some(new_settings) = reload_app_settings() else { return };
+            apply_settings_to_all(&tab_view, &new_settings);
+            header_bar.set_visible(new_settings.title_bar_visible);
+            toggle_title_bar_action.set_state(&glib::Variant::from(new_settings.title_bar_visible));
+            if let Some(app) = window_weak.upgrade().and_then(|window| window.application()) {
+                keybindings::apply_keybindings(&app, &new_settings.keybindings);
+            }
+            *app_settings_for_reload.borrow_mut() = new_settings;
+            let _ = header_bar.activate_action("win.update_title", None);
+        });
+        *pending_reload.borrow_mut() = Some(source);
+    });
+
+    window.connect_destroy(move |_| {
+        monitor.cancel();
+    });
+}
+
+/// Re-applies colors whenever the system switches between light and dark, so
+/// the configured light and dark presets take turns.
+fn follow_system_style(window: &ApplicationWindow, tab_view: &TabView, app_settings_rc: &Rc<RefCell<AppSettings>>) {
+    let style_manager = libadwaita::StyleManager::default();
+    let tab_view_weak = tab_view.downgrade();
+    let app_settings_for_style = Rc::clone(app_settings_rc);
+    let dark_handler = style_manager.connect_dark_notify(move |_| {
+        let Some(tab_view) = tab_view_weak.upgrade() else { return };
+        apply_settings_to_all(&tab_view, &app_settings_for_style.borrow());
+    });
+
+    // The style manager outlives the window.
+    let dark_handler = RefCell::new(Some(dark_handler));
+    window.connect_destroy(move |_| {
+        if let Some(handler) = dark_handler.take() {
+            libadwaita::StyleManager::default().disconnect(handler);
+        }
+    });
+}
+
+/// `colors` or the light or dark preset, whichever the system style calls for.
+fn style_colors(app_settings: &AppSettings) -> ColorSettings {
+    app_settings.colors_for_style(libadwaita::StyleManager::default().is_dark())
+}
+
+fn build_context_menu(app_settings: &AppSettings) -> gio::Menu {
+    let clipboard_section = gio::Menu::new();
+    clipboard_section.append(Some("Copy"), Some("win.copy"));
+    clipboard_section.append(Some("Copy as HTML"), Some("win.copy_html"));
+    clipboard_section.append(Some("Paste"), Some("win.paste"));
+    clipboard_section.append(Some("Paste Selection"), Some("win.paste_selection"));
+    clipboard_section.append(Some("Copy Link Address"), Some("term.copy_link"));
+    clipboard_section.append(Some("Find…"), Some("win.find"));
+
+    let menu = gio::Menu::new();
+    menu.append_section(None, &clipboard_section);
+    menu.append(Some("New Tab"), Some("win.new_tab"));
+    menu.append(Some("New Window"), Some("win.new_window"));
+    if !app_settings.profiles.is_empty() {
+        let new_tab_menu = gio::Menu::new();
+        let new_window_menu = gio::Menu::new();
+        for name in app_settings.profiles.keys() {
+            let label = if app_settings.default_profile.as_deref() == Some(name.as_str()) {
+                format!("{} (default)", name)
+            } else {
+                name.clone()
+            };
+            let target = glib::Variant::from(name.as_str());
+            let tab_item = gio::MenuItem::new(Some(&label), None);
+            tab_item.set_action_and_target_value(Some("win.new_tab_with_profile"), Some(&target));
+            new_tab_menu.append_item(&tab_item);
+            let window_item = gio::MenuItem::new(Some(&label), None);
+            window_item.set_action_and_target_value(Some("win.new_window_with_profile"), Some(&target));
+            new_window_menu.append_item(&window_item);
+        }
+        menu.append_submenu(Some("New Tab with Profile"), &new_tab_menu);
+        menu.append_submenu(Some("New Window with Profile"), &new_window_menu);
+    }
+    menu.append(Some("Set Title…"), Some("win.set_title"));
+    menu.append(Some("Close Tab"), Some("win.close_tab"));
+    menu.append(Some("Split Right"), Some("win.split_right"));
+    menu.append(Some("Split Down"), Some("win.split_down"));
+    menu.append(Some("Zoom Pane"), Some("win.zoom_pane"));
+    menu.append(Some("Close Pane"), Some("win.close_pane"));
+    menu.append(Some("Show Title Bar"), Some("win.toggle_title_bar"));
+    menu.append(Some("Settings"), Some("win.open_settings")); 
+    menu
+}
+
+// `profile` is a name already resolved with `AppSettings::profile_name`.
+fn create_terminal(app_settings: &AppSettings, profile: Option<&str>, shell: &ShellSettings) -> Terminal {
+    let terminal = Terminal::new();
+    terminal.set_hexpand(true);
+    terminal.set_vexpand(true);
+
+    set_terminal_profile(&terminal, profile);
+    apply_settings_to_terminal(&terminal, app_settings);
+    spawn_shell(&terminal, shell);
+
+    // VTE puts selected text in the PRIMARY selection on its own. Matches
+    // selected from the search bar happen while the terminal is unfocused
+    // and are left out.
+    terminal.connect_selection_changed(|terminal| {
+        if terminal_copy_on_select(terminal) && terminal.has_selection() && terminal.has_focus() {
+            terminal.copy_clipboard_format(vte4::Format::Text);
+        }
+    });
+
+    let popover = PopoverMenu::from_model(Some(&build_context_menu(app_settings)));
+    popover.set_parent(&terminal);
+
+    // The link under the pointer when the menu was opened, for "Copy Link
+    // Address".
+    let context_link: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
+    let copy_link_action = gio::SimpleAction::new("copy_link", None);
+    let terminal_weak_for_copy_link = terminal.downgrade();
+    let context_link_clone_for_copy = Rc::clone(&context_link);
+    copy_link_action.connect_activate(move |_, _| {
+        if let (Some(terminal), Some(address)) = (terminal_weak_for_copy_link.upgrade(), context_link_clone_for_copy.borrow().as_ref()) {
+            terminal.clipboard().set_text(address);
+        }
+    });
+    let terminal_actions = gio::SimpleActionGroup::new();
+    terminal_actions.add_action(&copy_link_action);
+    terminal.insert_action_group("term", Some(&terminal_actions));
+
+    let gesture = GestureClick::new();
+    gesture.set_button(3);
+    let popover_clone = popover.clone();
+    let terminal_weak_for_menu = terminal.downgrade();
+    gesture.connect_pressed(move |_, _, x, y| {
+        if let Some(terminal) = terminal_weak_for_menu.upgrade() {
+            let address = links::hyperlink_at(&terminal, x, y).or_else(|| {
+                links::link_at(&terminal, x, y, terminal_directory(&terminal).as_deref()).map(|target| target.address())
+            });
+            copy_link_action.set_enabled(address.is_some());
+            context_link.replace(address);
+        }
+        let rect = gtk4::gdk::Rectangle::new(x as i32, y as i32, 1, 1);
+        popover_clone.set_pointing_to(Some(&rect));
+        popover_clone.popup();
+    });
+    terminal.add_controller(gesture);
+
+    // Ctrl+click opens the link under the pointer.
+    let link_gesture = GestureClick::new();
+    link_gesture.set_button(1);
+    link_gesture.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    let terminal_weak_for_links = terminal.downgrade();
+    link_gesture.connect_pressed(move |gesture, _, x, y| {
+        if !gesture.current_event_state().contains(gdk::ModifierType::CONTROL_MASK) {
+            return;
+        }
+        let Some(terminal) = terminal_weak_for_links.upgrade() else { return };
+        let window = terminal.root().and_then(|root| root.downcast::<gtk4::Window>().ok());
+        if let Some(uri) = links::hyperlink_at(&terminal, x, y) {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            match links::host_mismatch(&terminal, x, y, &uri) {
+                Some(warning) => confirm_open_hyperlink(window.as_ref(), &uri, &warning),
+                None => links::open_link(window.as_ref(), &links::hyperlink_target(&uri)),
+            }
+        } else if let Some(target) = links::link_at(&terminal, x, y, terminal_directory(&terminal).as_deref()) {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            links::open_link(window.as_ref(), &target);
+        }
+    });
+    terminal.add_controller(link_gesture);
+
+    // Hyperlinks can show any text, so hovering one shows where it goes.
+    terminal.set_has_tooltip(true);
+    terminal.connect_query_tooltip(|terminal, x, y, _keyboard, tooltip| {
+        let Some(uri) = links::hyperlink_at(terminal, x as f64, y as f64) else { return false };
+        let text = match links::host_mismatch(terminal, x as f64, y as f64, &uri) {
+            Some(warning) => format!("{}\n\n⚠ {}", uri, warning),
+            None => uri,
+        };
+        tooltip.set_text(Some(&text));
+        true
+    });
+
+    terminal
+}
+
+/// The program and arguments `shell` describes.
+fn shell_argv(shell: &ShellSettings) -> Vec<String> {
+    let default_shell = shell
+        .env
+        .get("SHELL")
+        .cloned()
+        .or_else(|| std::env::var("SHELL").ok())
+        .unwrap_or_else(|| "/bin/sh".to_string());
+    let mut argv: Vec<String> = match shell.command.as_deref() {
+        Some(command) => match glib::shell_parse_argv(command) {
+            Ok(argv) => argv.into_iter().map(|arg| arg.to_string_lossy().into_owned()).collect(),
+            Err(e) => {
+                eprintln!("Failed to parse command \"{}\": {}", command, e);
+                vec![default_shell]
+            }
+        },
+        // bash, zsh and fish all read -l as "login shell".
+        None if shell.login => vec![default_shell, "-l".to_string()],
+        None => vec![default_shell],
+    };
+    argv.extend(shell.args.iter().cloned());
+    argv
+}
+
+/// The environment `shell` describes, as `NAME=value` pairs. It is passed to
+/// VTE whole, so that `unset_env` can take variables away without wrapping
+/// the command in env(1).
+fn shell_envv(shell: &ShellSettings) -> Vec<String> {
+    let mut env: BTreeMap<String, String> = BTreeMap::new();
+    if !shell.replace_environment {
+        env.extend(
+            std::env::vars_os()
+                .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+                .filter(|(name, _)| !VTE_ENVIRONMENT.contains(&name.as_str())),
+        );
+    }
+    env.extend(shell.env.iter().map(|(name, value)| (name.clone(), value.clone())));
+    for name in &shell.unset_env {
+        env.remove(name);
+    }
+    env.into_iter().map(|(name, value)| format!("{}={}", name, value)).collect()
+}
+
+// Spawn flag from vte/vteenums.h that keeps VTE from merging our own
+// environment into `envv`.
+const VTE_SPAWN_NO_PARENT_ENVV: glib::SpawnFlags = glib::SpawnFlags::from_bits_retain(1 << 25);
+
+fn spawn_shell(terminal: &Terminal, shell: &ShellSettings) {
+    set_terminal_shell(terminal, shell);
+    let argv = shell_argv(shell);
+    let terminal_weak = terminal.downgrade();
+    let working_directory = shell.working_directory.as_deref().map(expand_home);
+    let envv = shell_envv(shell);
+
+    let argv_refs: Vec<&str> = argv.iter().map(String::as_str).collect();
+    let envv_refs: Vec<&str> = envv.iter().map(String::as_str).collect();
+    terminal.spawn_async(
+        vte4::PtyFlags::DEFAULT,
+        working_directory.as_deref(),
+        &argv_refs,
+        &envv_refs,
+        glib::SpawnFlags::SEARCH_PATH | VTE_SPAWN_NO_PARENT_ENVV,
+        || {},
+        -1,
+        None::<&gio::Cancellable>,
+        move |result| {
+            let Some(terminal) = terminal_weak.upgrade() else { return };
+            match result {
+                Ok(pid) => set_terminal_child(&terminal, Some(TerminalChild { pid, started: Instant::now() })),
+                Err(e) => {
+                    eprintln!("Failed to spawn shell: {}", e);
+                    show_exit_banner(&terminal, &format!("Failed to start: {}", e.message()));
+                }
+            }
+        },
+    );
+}
+
+// The settings a terminal's child was started with, to relaunch it.
+const TERMINAL_SHELL_KEY: &str = "better-terminal-shell";
+
+fn set_terminal_shell(terminal: &Terminal, shell: &ShellSettings) {
+    // SAFETY: this key only ever holds a `ShellSettings`.
+    unsafe { terminal.set_data(TERMINAL_SHELL_KEY, shell.clone()) };
+}
+
+fn terminal_shell(terminal: &Terminal) -> ShellSettings {
+    // SAFETY: see `set_terminal_shell`.
+    unsafe {
+        terminal
+            .data::<ShellSettings>(TERMINAL_SHELL_KEY)
+            .map(|shell| shell.as_ref().clone())
+            .unwrap_or_default()
+    }
+}
+
+/// The running child of a terminal. Its pid is used when the shell does not
+/// report its directory.
+#[derive(Debug, Clone, Copy)]
+struct TerminalChild {
+    pid: glib::Pid,
+    started: Instant,
+}
+
+const TERMINAL_CHILD_KEY: &str = "better-terminal-child";
+
+// Children that exit sooner than this are not restarted.
+const MIN_RESTART_UPTIME: Duration = Duration::from_secs(1);
+
+fn set_terminal_child(terminal: &Terminal, child: Option<TerminalChild>) {
+    // SAFETY: this key only ever holds an `Option<TerminalChild>`.
+    unsafe { terminal.set_data(TERMINAL_CHILD_KEY, child) };
+}
+
+fn terminal_child(terminal: &Terminal) -> Option<TerminalChild> {
+    // SAFETY: see `set_terminal_child`.
+    unsafe {
+        terminal
+            .data::<Option<TerminalChild>>(TERMINAL_CHILD_KEY)
+            .and_then(|child| *child.as_ref())
+    }
+}
+
+fn terminal_pid(terminal: &Terminal) -> Option<glib::Pid> {
+    terminal_child(terminal).map(|child| child.pid)
+}
+
+/// `status` as returned by waitpid(), in words.
+fn describe_exit_status(status: i32) -> String {
+    // WIFEXITED/WEXITSTATUS and WTERMSIG, without pulling in libc.
+    if status & 0x7f == 0 {
+        format!("Process exited with status {}", (status >> 8) & 0xff)
+    } else {
+        format!("Process was killed by signal {}", status & 0x7f)
+    }
+}
+
+fn exit_banner(terminal: &Terminal) -> Option<Banner> {
+    panes::pane_of(terminal)?.first_child()?.downcast::<Banner>().ok()
+}
+
+fn show_exit_banner(terminal: &Terminal, title: &str) {
+    if let Some(banner) = exit_banner(terminal) {
+        banner.set_title(title);
+        banner.set_revealed(true);
+    }
+}
+
+fn relaunch(terminal: &Terminal) {
+    if let Some(banner) = exit_banner(terminal) {
+        banner.set_revealed(false);
+    }
+    terminal.feed(b"\r\n");
+    spawn_shell(terminal, &terminal_shell(terminal));
+}
+
+/// The directory `terminal`'s shell is in. VTE keeps what the shell last
+/// reported with OSC 7; shells that do not report it are looked up in /proc.
+fn terminal_directory(terminal: &Terminal) -> Option<String> {
+    if let Some(uri) = terminal.current_directory_uri() {
+        if let Ok((path, host)) = glib::filename_from_uri(&uri) {
+            // A directory on another machine, e.g. reported over SSH.
+            if links::is_local_host(host.as_ref().map(|host| host.as_str())) {
+                return Some(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+    let pid = terminal_pid(terminal)?;
+    let path = std::fs::read_link(format!("/proc/{}/cwd", pid.0)).ok()?;
+    Some(path.to_string_lossy().into_owned())
+}
+
+/// The process in the foreground of `terminal`'s PTY, as its pid and name:
+/// the shell itself while it waits at a prompt.
+fn foreground_process(terminal: &Terminal) -> Option<(i32, String)> {
+    let pid = terminal_pid(terminal)?;
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid.0)).ok()?;
+    // The name in parentheses may contain spaces; the fields after it are
+    // state, ppid, pgrp, session, tty_nr and tpgid.
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let foreground_group: i32 = fields.get(5)?.parse().ok()?;
+    if foreground_group <= 0 {
+        return None;
+    }
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", foreground_group)).ok()?;
+    Some((foreground_group, name.trim_end().to_string()))
+}
+
+/// Names of the programs other than the shells running in `tab_view`'s
+/// terminals, leaving out those named in `ignore`.
+fn running_programs(tab_view: &TabView, ignore: &[String]) -> Vec<String> {
+    tab_view_terminals(tab_view)
+        .iter()
+        .filter_map(|terminal| {
+            let shell_pid = terminal_pid(terminal)?;
+            let (pid, name) = foreground_process(terminal)?;
+            (pid != shell_pid.0 && !ignore.contains(&name)).then_some(name)
+        })
+        .collect()
+}
+
+fn confirm_close_window(window: &ApplicationWindow, running: &[String], close_confirmed: &Rc<Cell<bool>>) {
+    let body = format!(
+        "Closing the window will end these programs:\n\n{}",
+        running.iter().map(|name| format!("• {}", name)).collect::<Vec<_>>().join("\n")
+    );
+    let dialog = MessageDialog::new(Some(window), Some("Close Window?"), Some(&body));
+    dialog.add_responses(&[("cancel", "_Cancel"), ("close", "C_lose Window")]);
+    dialog.set_response_appearance("close", ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("cancel"));
+    dialog.set_close_response("cancel");
+
+    let window_weak = window.downgrade();
+    let close_confirmed = Rc::clone(close_confirmed);
+    dialog.connect_response(Some("close"), move |_, _| {
+        close_confirmed.set(true);
+        if let Some(window) = window_weak.upgrade() {
+            window.close();
+        }
+    });
+    dialog.present();
+}
+
+fn confirm_open_hyperlink(window: Option<&gtk4::Window>, uri: &str, warning: &str) {
+    let body = format!("{}\n\n{}", warning, uri);
+    let dialog = MessageDialog::new(window, Some("Open Link?"), Some(&body));
+    dialog.add_responses(&[("cancel", "_Cancel"), ("open", "_Open Link")]);
+    dialog.set_response_appearance("open", ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("cancel"));
+    dialog.set_close_response("cancel");
+
+    let uri = uri.to_string();
+    let window = window.cloned();
+    dialog.connect_response(Some("open"), move |_, _| {
+        links::open_link(window.as_ref(), &links::hyperlink_target(&uri));
+    });
+    dialog.present();
+}
+
+fn confirm_paste(window: &ApplicationWindow, terminal: &Terminal, text: &str, warnings: &[&str]) {
+    let dialog = MessageDialog::new(Some(window), Some("Paste Text?"), Some(&warnings.join("\n")));
+
+    // Control characters are shown as their symbols, e.g. ␛.
+    let preview_text: String = text
+        .chars()
+        .map(|c| match c {
+            '\n' | '\t' => c,
+            '\u{0}'..='\u{1f}' => char::from_u32(0x2400 + c as u32).unwrap_or(c),
+            '\u{7f}' => '␡',
+            _ => c,
+        })
+        .collect();
+    let preview = gtk4::TextView::new();
+    preview.set_editable(false);
+    preview.set_cursor_visible(false);
+    preview.set_monospace(true);
+    preview.buffer().set_text(&preview_text);
+    let scrolled_window = gtk4::ScrolledWindow::new();
+    scrolled_window.set_child(Some(&preview));
+    scrolled_window.set_min_content_width(360);
+    scrolled_window.set_max_content_height(200);
+    scrolled_window.set_propagate_natural_height(true);
+    dialog.set_extra_child(Some(&scrolled_window));
+
+    dialog.add_responses(&[("cancel", "_Cancel"), ("single_line", "Paste as _Single Line"), ("paste", "_Paste Anyway")]);
+    dialog.set_response_appearance("single_line", ResponseAppearance::Suggested);
+    dialog.set_response_appearance("paste", ResponseAppearance::Destructive);
+    dialog.set_default_response(Some("cancel"));
+    dialog.set_close_response("cancel");
+
+    let terminal = terminal.clone();
+    let text = text.to_string();
+    dialog.connect_response(None, move |_, response| match response {
+        "single_line" => terminal.paste_text(&paste::single_line(&text)),
+        "paste" => terminal.paste_text(&text),
+        _ => {}
+    });
+    dialog.present();
+}
+
+/// Launch options for a window opened from `window`: it starts where the
+/// active terminal is.
+fn inherited_launch_options(window: &ApplicationWindow, tab_view: &TabView) -> LaunchOptions {
+    LaunchOptions {
+        caller_directory: active_terminal(window, tab_view).and_then(|terminal| terminal_directory(&terminal)),
+        ..LaunchOptions::default()
+    }
+}
+
+// Terminals remember their profile so reloads re-apply the right settings.
+const TERMINAL_PROFILE_KEY: &str = "better-terminal-profile";
+
+fn set_terminal_profile(terminal: &Terminal, profile: Option<&str>) {
+    // SAFETY: this key only ever holds an `Option<String>`.
+    unsafe { terminal.set_data(TERMINAL_PROFILE_KEY, profile.map(str::to_string)) };
+}
+
+fn terminal_profile(terminal: &Terminal) -> Option<String> {
+    // SAFETY: see `set_terminal_profile`.
+    unsafe {
+        terminal
+            .data::<Option<String>>(TERMINAL_PROFILE_KEY)
+            .and_then(|profile| profile.as_ref().clone())
+    }
+}
+
+fn add_tab(tab_view: &TabView, app_settings: &AppSettings, profile: Option<&str>) -> Terminal {
+    add_tab_with_shell(tab_view, app_settings, profile, &app_settings.shell_settings(profile))
+}
+
+fn add_tab_with_shell(tab_view: &TabView, app_settings: &AppSettings, profile: Option<&str>, shell: &ShellSettings) -> Terminal {
+    let terminal = create_pane_terminal(tab_view, app_settings, profile, shell);
+
+    let root = panes::new_pane_root(&terminal);
+    let page = tab_view.append(&root);
+    page.set_title("Terminal");
+
+    tab_view.set_selected_page(&page);
+    terminal.grab_focus();
+    terminal
+}
+
+fn split_pane(tab_view: &TabView, terminal: &Terminal, orientation: Orientation, app_settings: &AppSettings) {
+    let profile = terminal_profile(terminal);
+    let shell = app_settings.shell_settings(profile.as_deref());
+    let new_terminal = create_pane_terminal(tab_view, app_settings, profile.as_deref(), &shell);
+    panes::split(terminal, orientation, &new_terminal);
+    let _ = tab_view.activate_action("win.update_pane_actions", None);
+}
+
+// Creates a terminal that lives in a tab's pane tree: it drives the tab title
+// while focused and removes itself from the tree when its child exits.
+fn create_pane_terminal(tab_view: &TabView, app_settings: &AppSettings, profile: Option<&str>, shell: &ShellSettings) -> Terminal {
+    let terminal = create_terminal(app_settings, profile, shell);
+    // New tabs and panes keep the window's zoom.
+    let zoom = tab_view_terminals(tab_view).first().map(terminal_zoom).unwrap_or(0.0);
+    if zoom != 0.0 {
+        set_terminal_zoom(&terminal, zoom);
+        apply_font_to_terminal(&terminal, app_settings);
+    }
+
+    // Shown by `show_exit_banner` once the child has exited or failed to
+    // start, for terminals that are not closed then.
+    let pane = panes::new_pane(&terminal);
+    let exit_banner = Banner::new("");
+    exit_banner.set_use_markup(false);
+    exit_banner.set_button_label(Some("Relaunch"));
+    let terminal_weak = terminal.downgrade();
+    exit_banner.connect_button_clicked(move |_| {
+        if let Some(terminal) = terminal_weak.upgrade() {
+            relaunch(&terminal);
+        }
+    });
+    pane.prepend(&exit_banner);
+
+    let tab_view_weak = tab_view.downgrade();
+    terminal.connect_window_title_changed(move |terminal| {
+        let Some(tab_view) = tab_view_weak.upgrade() else { return };
+        let Some(root) = panes::pane_root(terminal) else { return };
+        if terminal.has_focus() || panes::terminals_in(&root).len() == 1 {
+            update_tab_title(&tab_view, &root, terminal);
+        }
+        let _ = terminal.activate_action("win.update_title", None);
+    });
+    terminal.connect_current_directory_uri_changed(|terminal| {
+        let _ = terminal.activate_action("win.update_title", None);
+    });
+
+    let focus_controller = gtk4::EventControllerFocus::new();
+    let tab_view_weak = tab_view.downgrade();
+    let terminal_weak = terminal.downgrade();
+    focus_controller.connect_enter(move |_| {
+        let (Some(tab_view), Some(terminal)) = (tab_view_weak.upgrade(), terminal_weak.upgrade()) else { return };
+        if let Some(root) = panes::pane_root(&terminal) {
+            update_tab_title(&tab_view, &root, &terminal);
+        }
+        let _ = terminal.activate_action("win.update_title", None);
+    });
+    terminal.add_controller(focus_controller);
+
+    let tab_view_weak = tab_view.downgrade();
+    terminal.connect_child_exited(move |terminal, status| {
+        let child = terminal_child(terminal);
+        set_terminal_child(terminal, None);
+        match terminal_shell(terminal).exit_behavior {
+            ExitBehavior::Close => {}
+            ExitBehavior::Hold => {
+                show_exit_banner(terminal, &describe_exit_status(status));
+                return;
+            }
+            // A command that dies right away would restart in a busy loop.
+            ExitBehavior::Restart if child.is_some_and(|child| child.started.elapsed() < MIN_RESTART_UPTIME) => {
+                show_exit_banner(terminal, &format!("{}, not restarting", describe_exit_status(status)));
+                return;
+            }
+            ExitBehavior::Restart => {
+                relaunch(terminal);
+                return;
+            }
+        }
+        let Some(tab_view) = tab_view_weak.upgrade() else { return };
+        let Some(root) = panes::pane_root(terminal) else { return };
+        if panes::close_pane(terminal) {
+            tab_view.close_page(&tab_view.page(&root));
+        }
+        let _ = tab_view.activate_action("win.update_pane_actions", None);
+    });
+
+    terminal
+}
+
+fn update_tab_title(tab_view: &TabView, root: &Box, terminal: &Terminal) {
+    if let Some(title) = pinned_tab_title(root) {
+        tab_view.page(root).set_title(&title);
+    } else if let Some(title) = terminal.window_title() {
+        tab_view.page(root).set_title(&title);
+    }
+}
+
+// A title set with "Set Title…", which the terminals' own titles do not
+// replace.
+const TAB_PINNED_TITLE_KEY: &str = "better-terminal-pinned-title";
+
+fn pinned_tab_title(root: &Box) -> Option<String> {
+    // SAFETY: this key only ever holds an `Option<String>`.
+    unsafe {
+        root.data::<Option<String>>(TAB_PINNED_TITLE_KEY)
+            .and_then(|title| title.as_ref().clone())
+    }
+}
+
+/// Pins `title` on the tab whose pane tree is `root`, or goes back to the
+/// terminal's title for `None`.
+fn pin_tab_title(tab_view: &TabView, root: &Box, title: Option<&str>) {
+    // SAFETY: see `pinned_tab_title`.
+    unsafe { root.set_data(TAB_PINNED_TITLE_KEY, title.map(str::to_string)) };
+    let terminal = panes::terminals_in(root)
+        .into_iter()
+        .find(|terminal| terminal.has_focus())
+        .or_else(|| panes::terminals_in(root).into_iter().next());
+    match (title, terminal) {
+        (Some(title), _) => tab_view.page(root).set_title(title),
+        (None, Some(terminal)) => update_tab_title(tab_view, root, &terminal),
+        (None, None) => {}
+    }
+    let _ = tab_view.activate_action("win.update_title", None);
+}
+
+fn show_set_title_dialog(window: &ApplicationWindow, tab_view: &TabView, page: &libadwaita::TabPage) {
+    let Ok(root) = page.child().downcast::<Box>() else { return };
+
+    let dialog = MessageDialog::new(Some(window), Some("Set Title"), Some("Leave empty to use the title from the terminal."));
+    let entry = gtk4::Entry::new();
+    entry.set_text(&pinned_tab_title(&root).unwrap_or_else(|| page.title().to_string()));
+    entry.set_activates_default(true);
+    dialog.set_extra_child(Some(&entry));
+    dialog.add_responses(&[("cancel", "_Cancel"), ("set", "_Set")]);
+    dialog.set_response_appearance("set", ResponseAppearance::Suggested);
+    dialog.set_default_response(Some("set"));
+    dialog.set_close_response("cancel");
+
+    let tab_view = tab_view.clone();
+    dialog.connect_response(Some("set"), move |_, _| {
+        let title = entry.text();
+        let title = title.trim();
+        pin_tab_title(&tab_view, &root, if title.is_empty() { None } else { Some(title) });
+    });
+    dialog.present();
+}
+
+/// The window title for the selected tab: its pinned title, or
+/// `title_template` filled in from its active terminal.
+fn window_title_text(window: &ApplicationWindow, tab_view: &TabView, title_template: &str) -> String {
+    let Some(page) = tab_view.selected_page() else { return "Better Terminal".to_string() };
+    if let Some(title) = page.child().downcast::<Box>().ok().and_then(|root| pinned_tab_title(&root)) {
+        return title;
+    }
+    let Some(terminal) = active_terminal(window, tab_view) else { return "Better Terminal".to_string() };
+
+    let title = terminal
+        .window_title()
+        .map(|title| title.to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| "Better Terminal".to_string());
+    let cwd = terminal_directory(&terminal).map(|dir| home_to_tilde(&dir)).unwrap_or_default();
+    let process = foreground_process(&terminal).map(|(_, name)| name).unwrap_or_default();
+    title_template
+        .replace("{title}", &title)
+        .replace("{cwd}", &cwd)
+        .replace("{process}", &process)
+}
+
+fn home_to_tilde(path: &str) -> String {
+    let Some(home) = dirs::home_dir() else { return path.to_string() };
+    let home = home.to_string_lossy();
+    match path.strip_prefix(home.as_ref()) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{}", rest),
+        _ => path.to_string(),
+    }
+}
+
+fn tab_view_terminals(tab_view: &TabView) -> Vec<Terminal> {
+    let mut terminals = Vec::new();
+    for i in 0..tab_view.n_pages() {
+        terminals.extend(panes::terminals_in(&tab_view.nth_page(i).child()));
+    }
+    terminals
+}
+
+fn active_terminal(window: &ApplicationWindow, tab_view: &TabView) -> Option<Terminal> {
+    // The context menu popover is parented to its terminal, so this also
+    // resolves correctly while an item from it is being activated.
+    let focused = gtk4::prelude::GtkWindowExt::focus(window)
+        .and_then(|widget| widget.ancestor(Terminal::static_type()))
+        .and_then(|widget| widget.downcast::<Terminal>().ok());
+    if let Some(terminal) = focused {
+        return Some(terminal);
+    }
+    let page = tab_view.selected_page()?;
+    panes::terminals_in(&page.child()).into_iter().next()
+}
+
+/// Applies colors, font and scrollback from `app_settings` and the terminal's
+/// profile.
+fn apply_settings_to_terminal(terminal: &Terminal, app_settings: &AppSettings) {
+    let profile_name = terminal_profile(terminal);
+    let settings = app_settings.with_profile(profile_name.as_deref());
+    apply_color_settings(terminal, &style_colors(&settings));
+    apply_font_to_terminal(terminal, app_settings);
+
+    let profile = profile_name.as_deref().and_then(|name| app_settings.profiles.get(name));
+    if let Some(lines) = profile.and_then(|profile| profile.scrollback_lines) {
+        terminal.set_scrollback_lines(lines);
+    }
+
+    // SAFETY: this key only ever holds a `bool`.
+    unsafe { terminal.set_data(TERMINAL_COPY_ON_SELECT_KEY, app_settings.copy_on_select) };
+    terminal.set_allow_hyperlink(app_settings.hyperlinks);
+    links::add_link_matches(terminal, &app_settings.link_patterns);
+}
+
+const TERMINAL_COPY_ON_SELECT_KEY: &str = "better-terminal-copy-on-select";
+
+fn terminal_copy_on_select(terminal: &Terminal) -> bool {
+    // SAFETY: see `apply_settings_to_terminal`.
+    unsafe { terminal.data::<bool>(TERMINAL_COPY_ON_SELECT_KEY).is_some_and(|copy| *copy.as_ref()) }
+}
+
+fn apply_settings_to_all(tab_view: &TabView, app_settings: &AppSettings) {
+    for terminal in tab_view_terminals(tab_view) {
+        apply_settings_to_terminal(&terminal, app_settings);
+    }
+}
+
+const MIN_FONT_SIZE: f64 = 6.0;
+const MAX_FONT_SIZE: f64 = 72.0;
+
+/// Sets the font of `terminal`'s profile, zoomed by the window's zoom.
+fn apply_font_to_terminal(terminal: &Terminal, app_settings: &AppSettings) {
+    let settings = app_settings.with_profile(terminal_profile(terminal).as_deref());
+    let font_size = (settings.font_size + terminal_zoom(terminal)).clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+    let font_desc = pango::FontDescription::from_string(&format!("{} {}", settings.font_family, font_size));
+    terminal.set_font(Some(&font_desc));
+}
+
+const TERMINAL_ZOOM_KEY: &str = "better-terminal-zoom";
+
+/// Records how many points `terminal` is zoomed in from its profile's size.
+fn set_terminal_zoom(terminal: &Terminal, zoom: f64) {
+    // SAFETY: this key only ever holds an `f64`.
+    unsafe { terminal.set_data(TERMINAL_ZOOM_KEY, zoom) };
+}
+
+fn terminal_zoom(terminal: &Terminal) -> f64 {
+    // SAFETY: see `set_terminal_zoom`.
+    unsafe { terminal.data::<f64>(TERMINAL_ZOOM_KEY).map(|zoom| *zoom.as_ref()).unwrap_or(0.0) }
+}
+
+fn apply_font_to_all(tab_view: &TabView, font_family: &str, font_size: f64) {
+    let font_desc = pango::FontDescription::from_string(&format!("{} {}", font_family, font_size));
+    for terminal in tab_view_terminals(tab_view) {
+        terminal.set_font(Some(&font_desc));
+    }
+}
+
+fn apply_color_settings_to_all(tab_view: &TabView, colors: &ColorSettings) {
+    for terminal in tab_view_terminals(tab_view) {
+        apply_color_settings(&terminal, colors);
+    }
+}
+
 fn apply_color_settings(terminal: &Terminal, colors: &ColorSettings) {
     if let Some(fg_str) = &colors.foreground {
         if let Ok(rgba) = fg_str.parse::<gdk::RGBA>() {
@@ -202,17 +1413,56 @@ fn apply_color_settings(terminal: &Terminal, colors: &ColorSettings) {
             palette_gdk.len()
         );
     }
+
+    // set_colors resets these, so they have to come last.
+    terminal.set_color_cursor(parse_optional_color("cursor", &colors.cursor).as_ref());
+    terminal.set_color_cursor_foreground(parse_optional_color("cursor_foreground", &colors.cursor_foreground).as_ref());
+    terminal.set_color_highlight(parse_optional_color("selection", &colors.selection).as_ref());
+    terminal.set_color_highlight_foreground(parse_optional_color("selection_foreground", &colors.selection_foreground).as_ref());
+    terminal.set_color_bold(parse_optional_color("bold", &colors.bold).as_ref());
 }
 
-fn build_settings_window(parent: &ApplicationWindow, terminal: &Terminal, window: &ApplicationWindow) {
+fn parse_optional_color(role: &str, color: &Option<String>) -> Option<gdk::RGBA> {
+    let color_str = color.as_ref()?;
+    match color_str.parse::<gdk::RGBA>() {
+        Ok(rgba) => Some(rgba),
+        Err(_) => {
+            eprintln!("Failed to parse {} color for apply: {}", role, color_str);
+            None
+        }
+    }
+}
+
+fn build_settings_window(window: &ApplicationWindow, tab_view: &TabView, app_settings_rc: &Rc<RefCell<AppSettings>>) {
     let current_colors = Rc::new(RefCell::new(load_color_settings()));
     let app_settings = load_app_settings();
     let current_font_family = Rc::new(RefCell::new(app_settings.font_family));
     let current_font_size = Rc::new(RefCell::new(app_settings.font_size));
+    let current_light_preset = Rc::new(RefCell::new(app_settings.light_preset));
+    let current_dark_preset = Rc::new(RefCell::new(app_settings.dark_preset));
+
+    // The settings as edited so far, for showing them before they are saved.
+    let edited_settings = {
+        let app_settings_rc = Rc::clone(app_settings_rc);
+        let current_colors = Rc::clone(&current_colors);
+        let current_font_family = Rc::clone(&current_font_family);
+        let current_font_size = Rc::clone(&current_font_size);
+        let current_light_preset = Rc::clone(&current_light_preset);
+        let current_dark_preset = Rc::clone(&current_dark_preset);
+        Rc::new(move || {
+            let mut settings = app_settings_rc.borrow().clone();
+            settings.colors = current_colors.borrow().clone();
+            settings.font_family = current_font_family.borrow().clone();
+            settings.font_size = *current_font_size.borrow();
+            settings.light_preset = current_light_preset.borrow().clone();
+            settings.dark_preset = current_dark_preset.borrow().clone();
+            settings
+        })
+    };
 
     let preferences_window = PreferencesWindow::builder()
         .title("Settings")
-        .transient_for(parent)
+        .transient_for(window)
         .modal(true)
         .build();
 
@@ -222,12 +1472,14 @@ fn build_settings_window(parent: &ApplicationWindow, terminal: &Terminal, window
         .title("Color Scheme Preset")
         .build();
 
-    let preset_names: Vec<&str> = ColorSchemePreset::all_presets().iter().map(|p| p.name()).collect();
-    let string_list = StringList::new(&preset_names);
-    let preset_dropdown = DropDown::new(Some(string_list), gtk4::Expression::NONE);
+    // Shared with the import button, which adds the themes it installs.
+    let presets = Rc::new(RefCell::new(ColorSchemePreset::all_presets()));
+    let preset_names: Vec<String> = presets.borrow().iter().map(|p| p.name().to_string()).collect();
+    let string_list = StringList::new(&preset_names.iter().map(String::as_str).collect::<Vec<&str>>());
+    let preset_dropdown = DropDown::new(Some(string_list.clone()), gtk4::Expression::NONE);
 
     if let Some(active_preset_name) = &current_colors.borrow().active_preset {
-        if let Some(pos) = preset_names.iter().position(|&name| name == active_preset_name) {
+        if let Some(pos) = preset_names.iter().position(|name| name == active_preset_name) {
             preset_dropdown.set_selected(pos as u32);
         } else {
             preset_dropdown.set_selected(gtk4::INVALID_LIST_POSITION);
@@ -242,7 +1494,77 @@ fn build_settings_window(parent: &ApplicationWindow, terminal: &Terminal, window
         .build();
     preset_row.add_suffix(&preset_dropdown);
     preset_group.add(&preset_row);
+
+    let import_button = gtk4::Button::with_label("Import…");
+    import_button.set_valign(gtk4::Align::Center);
+    let import_row = ActionRow::builder()
+        .title("Import Theme")
+        .subtitle("Alacritty, Kitty, iTerm2 or Xresources color scheme")
+        .activatable_widget(&import_button)
+        .build();
+    import_row.add_suffix(&import_button);
+    preset_group.add(&import_row);
+
+    let export_formats = ExportFormat::all();
+    let export_format_names: Vec<&str> = export_formats.iter().map(|format| format.name()).collect();
+    let export_format_dropdown = DropDown::from_strings(&export_format_names);
+    export_format_dropdown.set_valign(gtk4::Align::Center);
+    let export_button = gtk4::Button::with_label("Export…");
+    export_button.set_valign(gtk4::Align::Center);
+    let export_row = ActionRow::builder()
+        .title("Export Theme")
+        .subtitle("Save the current colors for another terminal")
+        .build();
+    export_row.add_suffix(&export_format_dropdown);
+    export_row.add_suffix(&export_button);
+    preset_group.add(&export_row);
     page.add(&preset_group);
+
+    let style_group = PreferencesGroup::builder()
+        .title("Light and Dark Style")
+        .description("Presets to switch to when the system style changes. Without one, the preset above is used.")
+        .build();
+
+    // "Custom" is a starting point for editing the colors below, not a
+    // scheme to switch to.
+    let style_presets: Vec<String> = preset_names.iter().filter(|name| *name != "Custom").cloned().collect();
+    let mut style_preset_names: Vec<&str> = vec!["None"];
+    style_preset_names.extend(style_presets.iter().map(String::as_str));
+    for (title, current_style_preset) in [
+        ("Light Style Preset", &current_light_preset),
+        ("Dark Style Preset", &current_dark_preset),
+    ] {
+        let style_dropdown = DropDown::from_strings(&style_preset_names);
+        style_dropdown.set_valign(gtk4::Align::Center);
+        let selected = current_style_preset
+            .borrow()
+            .as_ref()
+            .and_then(|name| style_presets.iter().position(|preset_name| preset_name == name))
+            .map_or(0, |pos| pos + 1);
+        style_dropdown.set_selected(selected as u32);
+
+        let style_row = ActionRow::builder()
+            .title(title)
+            .activatable_widget(&style_dropdown)
+            .build();
+        style_row.add_suffix(&style_dropdown);
+        style_group.add(&style_row);
+
+        let current_style_preset_clone = Rc::clone(current_style_preset);
+        let tab_view_clone_for_style = tab_view.clone();
+        let style_presets_clone_for_style = style_presets.clone();
+        let edited_settings_clone_for_style = Rc::clone(&edited_settings);
+        style_dropdown.connect_selected_notify(move |dropdown| {
+            let preset_name = (dropdown.selected() as usize)
+                .checked_sub(1)
+                .and_then(|index| style_presets_clone_for_style.get(index))
+                .cloned();
+            *current_style_preset_clone.borrow_mut() = preset_name;
+            // Also on "None", which goes back to the colors below.
+            apply_settings_to_all(&tab_view_clone_for_style, &edited_settings_clone_for_style());
+        });
+    }
+    page.add(&style_group);
     
     let general_group = PreferencesGroup::builder()
         .title("General Colors (Overrides Preset)")
@@ -274,6 +1596,69 @@ fn build_settings_window(parent: &ApplicationWindow, terminal: &Terminal, window
     bg_row.add_suffix(&bg_color_button);
     general_group.add(&bg_row);
 
+    // Roles VTE picks a color for by itself while they are unset.
+    let color_roles: [(&str, ColorRole); 5] = [
+        ("Cursor Color", |colors| &mut colors.cursor),
+        ("Cursor Text Color", |colors| &mut colors.cursor_foreground),
+        ("Selection Color", |colors| &mut colors.selection),
+        ("Selected Text Color", |colors| &mut colors.selection_foreground),
+        ("Bold Text Color", |colors| &mut colors.bold),
+    ];
+    let mut role_buttons: Vec<(ColorRole, ColorButton)> = Vec::new();
+    for (title, role) in color_roles {
+        let color_button = ColorButton::new();
+        let current_role_color = role(&mut current_colors.borrow_mut()).clone();
+        if let Some(color_str) = current_role_color {
+            if let Ok(rgba) = color_str.parse::<gdk::RGBA>() {
+                color_button.set_rgba(&rgba);
+            }
+        }
+        color_button.set_valign(gtk4::Align::Center);
+
+        let clear_button = gtk4::Button::from_icon_name("edit-clear-symbolic");
+        clear_button.set_valign(gtk4::Align::Center);
+        clear_button.set_tooltip_text(Some("Use the default color"));
+        clear_button.add_css_class("flat");
+
+        let row = ActionRow::builder()
+            .title(title)
+            .activatable_widget(&color_button)
+            .build();
+        row.add_suffix(&color_button);
+        row.add_suffix(&clear_button);
+        general_group.add(&row);
+
+        let tab_view_role_clone = tab_view.clone();
+        let preset_dropdown_clone_role = preset_dropdown.clone();
+        let current_colors_clone_role = Rc::clone(&current_colors);
+        color_button.connect_notify_local(Some("rgba"), move |button, _paramspec| {
+            let new_colors = {
+                let mut borrowed_current_colors = current_colors_clone_role.borrow_mut();
+                *role(&mut borrowed_current_colors) = Some(button.rgba().to_string());
+                borrowed_current_colors.active_preset = None;
+                borrowed_current_colors.clone()
+            };
+            preset_dropdown_clone_role.set_selected(gtk4::INVALID_LIST_POSITION);
+            apply_color_settings_to_all(&tab_view_role_clone, &new_colors);
+        });
+
+        let tab_view_clear_clone = tab_view.clone();
+        let preset_dropdown_clone_clear = preset_dropdown.clone();
+        let current_colors_clone_clear = Rc::clone(&current_colors);
+        clear_button.connect_clicked(move |_| {
+            let new_colors = {
+                let mut borrowed_current_colors = current_colors_clone_clear.borrow_mut();
+                *role(&mut borrowed_current_colors) = None;
+                borrowed_current_colors.active_preset = None;
+                borrowed_current_colors.clone()
+            };
+            preset_dropdown_clone_clear.set_selected(gtk4::INVALID_LIST_POSITION);
+            apply_color_settings_to_all(&tab_view_clear_clone, &new_colors);
+        });
+
+        role_buttons.push((role, color_button));
+    }
+
     // background opacity control
     let opacity_adjustment = gtk4::Adjustment::new(
         current_colors.borrow().background_opacity.unwrap_or(1.0),
@@ -350,7 +1735,7 @@ fn build_settings_window(parent: &ApplicationWindow, terminal: &Terminal, window
     font_group.add(&font_size_row);
     page.add(&font_group);
 
-    let terminal_clone_for_font = terminal.clone();
+    let tab_view_clone_for_font = tab_view.clone();
     let current_font_family_clone = Rc::clone(&current_font_family);
     let current_font_size_clone_for_font_button = Rc::clone(&current_font_size);
     let font_size_spin_clone = font_size_spin.clone();
@@ -364,66 +1749,183 @@ fn build_settings_window(parent: &ApplicationWindow, terminal: &Terminal, window
                 *current_font_size_clone_for_font_button.borrow_mut() = new_size;
                 font_size_spin_clone.set_value(new_size);
             }
-            let new_font_desc = format!("{} {}", current_font_family_clone.borrow(), current_font_size_clone_for_font_button.borrow());
-            let font_desc = pango::FontDescription::from_string(&new_font_desc);
-            terminal_clone_for_font.set_font(Some(&font_desc));
+            apply_font_to_all(&tab_view_clone_for_font, &current_font_family_clone.borrow(), *current_font_size_clone_for_font_button.borrow());
         }
     });
 
-    let terminal_clone_for_font_size = terminal.clone();
+    let tab_view_clone_for_font_size = tab_view.clone();
     let current_font_size_clone = Rc::clone(&current_font_size);
     let current_font_family_clone_for_size = Rc::clone(&current_font_family);
     font_size_spin.connect_value_changed(move |spin| {
         let new_size = spin.value();
         *current_font_size_clone.borrow_mut() = new_size;
-        let font_desc = pango::FontDescription::from_string(&format!("{} {}", current_font_family_clone_for_size.borrow(), new_size));
-        terminal_clone_for_font_size.set_font(Some(&font_desc));
+        apply_font_to_all(&tab_view_clone_for_font_size, &current_font_family_clone_for_size.borrow(), new_size);
     });
 
     let general_group_clone = general_group.clone();
     let ansi_group_clone = ansi_group.clone();
 
-    let terminal_clone_for_preset_apply = terminal.clone();
+    let tab_view_clone_for_preset_apply = tab_view.clone();
     let fg_button_clone_for_preset_update = fg_color_button.clone();
     let bg_button_clone_for_preset_update = bg_color_button.clone();
     let palette_buttons_clone_for_preset_update = palette_buttons.clone();
+    let role_buttons_clone_for_preset_update = role_buttons.clone();
     let current_colors_clone_for_preset = Rc::clone(&current_colors);
 
-    preset_dropdown.connect_selected_notify(move |dropdown| {
-        let selected_idx = dropdown.selected();
-        if let Some(preset) = ColorSchemePreset::all_presets().get(selected_idx as usize) {
-            let preset_settings = get_preset_colors(preset);
-            *current_colors_clone_for_preset.borrow_mut() = preset_settings.clone();
-
-            if preset.name() == "Custom" {
-                general_group_clone.set_visible(true);
-                ansi_group_clone.set_visible(true);
-            } else {
-                general_group_clone.set_visible(false);
-                ansi_group_clone.set_visible(false);
+    // Also called by the import button, which may select the theme that is
+    // already selected.
+    let apply_preset = Rc::new(move |preset: &ColorSchemePreset| {
+        let preset_settings = get_preset_colors(preset);
+        *current_colors_clone_for_preset.borrow_mut() = preset_settings.clone();
+
+        if preset.name() == "Custom" {
+            general_group_clone.set_visible(true);
+            ansi_group_clone.set_visible(true);
+        } else {
+            general_group_clone.set_visible(false);
+            ansi_group_clone.set_visible(false);
+        }
+        
+        if let Some(fg_str) = &preset_settings.foreground {
+            if let Ok(rgba) = fg_str.parse::<gdk::RGBA>() {
+                fg_button_clone_for_preset_update.set_rgba(&rgba);
             }
-            
-            if let Some(fg_str) = &preset_settings.foreground {
-                if let Ok(rgba) = fg_str.parse::<gdk::RGBA>() {
-                    fg_button_clone_for_preset_update.set_rgba(&rgba);
+        }
+        
+        if let Some(bg_str) = &preset_settings.background {
+            if let Ok(rgba) = bg_str.parse::<gdk::RGBA>() {
+                bg_button_clone_for_preset_update.set_rgba(&rgba);
+            }
+        }
+        
+        let mut preset_roles = preset_settings.clone();
+        for (role, role_button) in &role_buttons_clone_for_preset_update {
+            if let Some(color_str) = role(&mut preset_roles) {
+                if let Ok(rgba) = color_str.parse::<gdk::RGBA>() {
+                    role_button.set_rgba(&rgba);
                 }
             }
-            
-            if let Some(bg_str) = &preset_settings.background {
-                if let Ok(rgba) = bg_str.parse::<gdk::RGBA>() {
-                    bg_button_clone_for_preset_update.set_rgba(&rgba);
+        }
+
+        for (i, p_button) in palette_buttons_clone_for_preset_update.iter().enumerate() {
+            if let Some(Some(color_str)) = preset_settings.palette.get(i) {
+                if let Ok(rgba) = color_str.parse::<gdk::RGBA>() {
+                    p_button.set_rgba(&rgba);
                 }
             }
-            
-            for (i, p_button) in palette_buttons_clone_for_preset_update.iter().enumerate() {
-                if let Some(Some(color_str)) = preset_settings.palette.get(i) {
-                    if let Ok(rgba) = color_str.parse::<gdk::RGBA>() {
-                        p_button.set_rgba(&rgba);
+        }
+        apply_color_settings_to_all(&tab_view_clone_for_preset_apply, &preset_settings);
+    });
+
+    let presets_clone_for_preset = Rc::clone(&presets);
+    let apply_preset_clone_for_dropdown = Rc::clone(&apply_preset);
+    preset_dropdown.connect_selected_notify(move |dropdown| {
+        let selected_preset = presets_clone_for_preset.borrow().get(dropdown.selected() as usize).cloned();
+        if let Some(preset) = selected_preset {
+            apply_preset_clone_for_dropdown(&preset);
+        }
+    });
+
+    // The dialog has to outlive this handler, or it closes as soon as it is shown.
+    let import_dialog: Rc<RefCell<Option<gtk4::FileChooserNative>>> = Rc::new(RefCell::new(None));
+    let preferences_window_clone_for_import = preferences_window.clone();
+    let presets_clone_for_import = Rc::clone(&presets);
+    let preset_dropdown_clone_for_import = preset_dropdown.clone();
+    let apply_preset_clone_for_import = Rc::clone(&apply_preset);
+    import_button.connect_clicked(move |_| {
+        let dialog = gtk4::FileChooserNative::new(
+            Some("Import Theme"),
+            Some(&preferences_window_clone_for_import),
+            gtk4::FileChooserAction::Open,
+            Some("_Import"),
+            Some("_Cancel"),
+        );
+
+        let preferences_window = preferences_window_clone_for_import.clone();
+        let presets = Rc::clone(&presets_clone_for_import);
+        let preset_dropdown = preset_dropdown_clone_for_import.clone();
+        let string_list = string_list.clone();
+        let apply_preset = Rc::clone(&apply_preset_clone_for_import);
+        dialog.connect_response(move |dialog, response| {
+            if response != gtk4::ResponseType::Accept {
+                return;
+            }
+            let Some(path) = dialog.file().and_then(|file| file.path()) else {
+                return;
+            };
+            match import::install_theme_file(&path) {
+                Ok((name, _)) => {
+                    let preset = ColorSchemePreset::User(name.clone());
+                    let position = {
+                        let mut presets = presets.borrow_mut();
+                        match presets.iter().position(|other| *other == preset) {
+                            Some(position) => position,
+                            None => {
+                                presets.push(preset.clone());
+                                string_list.append(&name);
+                                presets.len() - 1
+                            }
+                        }
+                    };
+                    // Selecting the same entry again would not notify.
+                    if preset_dropdown.selected() == position as u32 {
+                        apply_preset(&preset);
+                    } else {
+                        preset_dropdown.set_selected(position as u32);
                     }
+                    preferences_window.add_toast(libadwaita::Toast::new(&format!("Imported \"{}\"", name)));
+                }
+                Err(e) => {
+                    eprintln!("Failed to import theme {}: {}", path.display(), e);
+                    preferences_window.add_toast(libadwaita::Toast::new(&format!("Import failed: {}", e)));
                 }
             }
-            apply_color_settings(&terminal_clone_for_preset_apply, &preset_settings);
-        }
+        });
+        dialog.show();
+        *import_dialog.borrow_mut() = Some(dialog);
+    });
+
+    let export_dialog: Rc<RefCell<Option<gtk4::FileChooserNative>>> = Rc::new(RefCell::new(None));
+    let preferences_window_clone_for_export = preferences_window.clone();
+    let edited_settings_clone_for_export = Rc::clone(&edited_settings);
+    export_button.connect_clicked(move |_| {
+        let Some(format) = export_formats.get(export_format_dropdown.selected() as usize).copied() else {
+            return;
+        };
+        // Export what the terminals show, which is the light or dark style
+        // preset when one is set.
+        let colors = style_colors(&edited_settings_clone_for_export());
+        let name = colors.active_preset.clone().unwrap_or_else(|| "Custom".to_string());
+
+        let dialog = gtk4::FileChooserNative::new(
+            Some("Export Theme"),
+            Some(&preferences_window_clone_for_export),
+            gtk4::FileChooserAction::Save,
+            Some("_Export"),
+            Some("_Cancel"),
+        );
+        dialog.set_current_name(&format.file_name(&name));
+
+        let preferences_window = preferences_window_clone_for_export.clone();
+        dialog.connect_response(move |dialog, response| {
+            if response != gtk4::ResponseType::Accept {
+                return;
+            }
+            let Some(path) = dialog.file().and_then(|file| file.path()) else {
+                return;
+            };
+            match export::export_theme_file(&path, &name, &colors, format) {
+                Ok(()) => {
+                    preferences_window.add_toast(libadwaita::Toast::new(&format!("Exported to {}", path.display())));
+                }
+                Err(e) => {
+                    eprintln!("Failed to export theme to {}: {}", path.display(), e);
+                    preferences_window.add_toast(libadwaita::Toast::new(&format!("Export failed: {}", e)));
+                }
+            }
+        });
+        dialog.show();
+        *export_dialog.borrow_mut() = Some(dialog);
     });
 
     if let Some(active_preset_name) = &current_colors.borrow().active_preset {
@@ -439,91 +1941,98 @@ fn build_settings_window(parent: &ApplicationWindow, terminal: &Terminal, window
         ansi_group.set_visible(true);
     }
 
-    let terminal_fg_clone = terminal.clone();
+    let tab_view_fg_clone = tab_view.clone();
     let preset_dropdown_clone_fg = preset_dropdown.clone();
     let current_colors_clone_fg = Rc::clone(&current_colors);
     fg_color_button.connect_notify_local(Some("rgba"), move |button, _paramspec| {
-        let rgba = button.rgba();
-        let mut new_colors = load_color_settings(); 
-        new_colors.foreground = Some(rgba.to_string());
-        new_colors.active_preset = None; 
-        preset_dropdown_clone_fg.set_selected(gtk4::INVALID_LIST_POSITION); 
-        apply_color_settings(&terminal_fg_clone, &new_colors);
-        
-        let mut borrowed_current_colors = current_colors_clone_fg.borrow_mut();
-        borrowed_current_colors.foreground = Some(rgba.to_string()); 
-        borrowed_current_colors.active_preset = None;
+        let new_colors = {
+            let mut borrowed_current_colors = current_colors_clone_fg.borrow_mut();
+            borrowed_current_colors.foreground = Some(button.rgba().to_string());
+            borrowed_current_colors.active_preset = None;
+            borrowed_current_colors.clone()
+        };
+        preset_dropdown_clone_fg.set_selected(gtk4::INVALID_LIST_POSITION);
+        apply_color_settings_to_all(&tab_view_fg_clone, &new_colors);
     });
 
-    let terminal_bg_clone = terminal.clone();
+    let tab_view_bg_clone = tab_view.clone();
     let preset_dropdown_clone_bg = preset_dropdown.clone();
     let current_colors_clone_bg = Rc::clone(&current_colors);
     bg_color_button.connect_notify_local(Some("rgba"), move |button, _paramspec| {
-        let rgba = button.rgba();
-        let mut new_colors = load_color_settings(); 
-        new_colors.background = Some(rgba.to_string());
-        new_colors.active_preset = None; 
+        let new_colors = {
+            let mut borrowed_current_colors = current_colors_clone_bg.borrow_mut();
+            borrowed_current_colors.background = Some(button.rgba().to_string());
+            borrowed_current_colors.active_preset = None;
+            borrowed_current_colors.clone()
+        };
         preset_dropdown_clone_bg.set_selected(gtk4::INVALID_LIST_POSITION);
-        apply_color_settings(&terminal_bg_clone, &new_colors);
-
-        let mut borrowed_current_colors = current_colors_clone_bg.borrow_mut();
-        borrowed_current_colors.background = Some(rgba.to_string()); 
-        borrowed_current_colors.active_preset = None;
+        apply_color_settings_to_all(&tab_view_bg_clone, &new_colors);
     });
 
-    let terminal_opacity_clone = terminal.clone();
+    let tab_view_opacity_clone = tab_view.clone();
     let preset_dropdown_clone_opacity = preset_dropdown.clone();
     let current_colors_clone_opacity = Rc::clone(&current_colors);
     let window_clone = window.clone();
     opacity_scale.connect_value_changed(move |scale| {
         window_clone.set_opacity(scale.value());
-        let opacity = scale.value();
-        let mut new_colors = load_color_settings();
-        new_colors.background_opacity = Some(opacity);
-        new_colors.active_preset = None;
+        let new_colors = {
+            let mut borrowed_current_colors = current_colors_clone_opacity.borrow_mut();
+            borrowed_current_colors.background_opacity = Some(scale.value());
+            borrowed_current_colors.active_preset = None;
+            borrowed_current_colors.clone()
+        };
         preset_dropdown_clone_opacity.set_selected(gtk4::INVALID_LIST_POSITION);
-        apply_color_settings(&terminal_opacity_clone, &new_colors);
-
-        let mut borrowed_current_colors = current_colors_clone_opacity.borrow_mut();
-        borrowed_current_colors.background_opacity = Some(opacity);
-        borrowed_current_colors.active_preset = None;
+        apply_color_settings_to_all(&tab_view_opacity_clone, &new_colors);
     });
 
     for (i, p_button) in palette_buttons.iter().enumerate() {
-        let p_button_clone = p_button.clone();
-        let terminal_palette_clone = terminal.clone();
+        let tab_view_palette_clone = tab_view.clone();
         let preset_dropdown_clone_palette = preset_dropdown.clone();
         let current_colors_clone_palette = Rc::clone(&current_colors);
-
-        p_button_clone.connect_notify_local(Some("rgba"), move |btn, _|
-            {
-                let rgba = btn.rgba();
-                let mut updated_colors = load_color_settings(); 
-                if i < updated_colors.palette.len() {
-                    updated_colors.palette[i] = Some(rgba.to_string());
-                }
-                updated_colors.active_preset = None; 
-                preset_dropdown_clone_palette.set_selected(gtk4::INVALID_LIST_POSITION);
-                apply_color_settings(&terminal_palette_clone, &updated_colors);
-                
+        p_button.connect_notify_local(Some("rgba"), move |button, _paramspec| {
+            let new_colors = {
                 let mut borrowed_current_colors = current_colors_clone_palette.borrow_mut();
                 if i < borrowed_current_colors.palette.len() {
-                    borrowed_current_colors.palette[i] = Some(rgba.to_string());
+                    borrowed_current_colors.palette[i] = Some(button.rgba().to_string());
                 }
                 borrowed_current_colors.active_preset = None;
-            }
-        );
+                borrowed_current_colors.clone()
+            };
+            preset_dropdown_clone_palette.set_selected(gtk4::INVALID_LIST_POSITION);
+            apply_color_settings_to_all(&tab_view_palette_clone, &new_colors);
+        });
     }
 
-    // Save font size to settings from setting
+    // Save colors and font to settings from setting
     let current_font_family_clone_for_save = Rc::clone(&current_font_family);
     let current_font_size_clone_for_save = Rc::clone(&current_font_size);
+    let app_settings_for_save = Rc::clone(app_settings_rc);
+    let tab_view_clone_for_save = tab_view.clone();
+    let window_clone_for_save = window.clone();
     preferences_window.connect_close_request(move |_window| {
-        let mut settings_to_save = current_colors.borrow().clone();
-        settings_to_save.active_preset = None; 
-        save_color_settings(&settings_to_save);
-        save_font_family_setting(&current_font_family_clone_for_save.borrow());
-        crate::config::save_font_size_setting(*current_font_size_clone_for_save.borrow());
+        let mut colors_to_save = current_colors.borrow().clone();
+        colors_to_save.active_preset = None; 
+        let font_family = current_font_family_clone_for_save.borrow().clone();
+        let font_size = *current_font_size_clone_for_save.borrow();
+        let light_preset = current_light_preset.borrow().clone();
+        let dark_preset = current_dark_preset.borrow().clone();
+
+        // New tabs and panes are created from these settings.
+        let mut app_settings = app_settings_for_save.borrow_mut();
+        app_settings.colors = colors_to_save.clone();
+        app_settings.font_family = font_family.clone();
+        app_settings.font_size = font_size;
+        app_settings.light_preset = light_preset.clone();
+        app_settings.dark_preset = dark_preset.clone();
+        apply_settings_to_all(&tab_view_clone_for_save, &app_settings);
+
+        save_app_settings(&window_clone_for_save, |settings| {
+            settings.colors = colors_to_save;
+            settings.font_family = font_family;
+            settings.font_size = font_size;
+            settings.light_preset = light_preset;
+            settings.dark_preset = dark_preset;
+        });
         glib::Propagation::Proceed
     });
 
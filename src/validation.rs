@@ -0,0 +1,563 @@
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use gtk4::gdk;
+use toml::{Table, Value};
+
+use crate::config::{self, ColorSchemePreset, CONFIG_VERSION, TITLE_PLACEHOLDERS};
+use crate::keybindings;
+
+/// A problem found in the config file, pointing at the offending line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigDiagnostic {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub key: String,
+    pub reason: String,
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if !self.key.is_empty() {
+            write!(f, ": {}", self.key)?;
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+struct Validator<'a> {
+    path: &'a Path,
+    contents: &'a str,
+    diagnostics: Vec<ConfigDiagnostic>,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, line: Option<usize>, key: &str, reason: String) {
+        self.diagnostics.push(ConfigDiagnostic {
+            path: self.path.to_path_buf(),
+            line,
+            key: key.to_string(),
+            reason,
+        });
+    }
+
+    fn report_toml_error(&mut self, error: &toml::de::Error) {
+        let (line, key) = match error.span() {
+            Some(span) => self.key_at(span),
+            None => (None, String::new()),
+        };
+        self.report(line, &key, error.message().trim().replace('\n', ", "));
+    }
+
+    fn key_at(&self, span: Range<usize>) -> (Option<usize>, String) {
+        let start = span.start.min(self.contents.len());
+        let line_index = self.contents[..start].matches('\n').count();
+        let key = self
+            .contents
+            .lines()
+            .nth(line_index)
+            .and_then(|line| line.split_once('='))
+            .map(|(key, _)| key.trim().to_string())
+            .unwrap_or_default();
+        (Some(line_index + 1), key)
+    }
+
+    /// Line of `key` inside `[table]`, found by scanning the text since the
+    /// parsed table does not keep positions.
+    fn key_line(&self, table: &str, key: &str) -> Option<usize> {
+        let mut current_table = String::new();
+        for (index, line) in self.contents.lines().enumerate() {
+            let trimmed = line.trim();
+            // `[[name]]` starts an entry of an array of tables.
+            if let Some(header) = trimmed.strip_prefix('[') {
+                current_table = header.trim_start_matches('[').split(']').next().unwrap_or("").trim().to_string();
+                continue;
+            }
+            if current_table == table {
+                if let Some((line_key, _)) = trimmed.split_once('=') {
+                    if line_key.trim() == key {
+                        return Some(index + 1);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Line of the `[table]` or `[[table]]` header.
+    fn table_line(&self, table: &str) -> Option<usize> {
+        self.contents.lines().position(|line| {
+            line.trim()
+                .strip_prefix('[')
+                .is_some_and(|header| header.trim_start_matches('[').split(']').next().unwrap_or("").trim() == table)
+        })
+        .map(|index| index + 1)
+    }
+
+    fn value_line_after(&self, start_line: Option<usize>, value: &str) -> Option<usize> {
+        let start_line = start_line?;
+        let quoted = format!("\"{}\"", value);
+        self.contents
+            .lines()
+            .enumerate()
+            .skip(start_line - 1)
+            .find(|(_, line)| line.contains(&quoted))
+            .map(|(index, _)| index + 1)
+            .or(Some(start_line))
+    }
+}
+
+// Keys of each table in the config file, as `ConfigFile` and the types in
+// it read them.
+const TOP_LEVEL_KEYS: [&str; 11] = [
+    "version", "default_profile", "window", "font", "colors", "shell", "appearance", "clipboard", "links", "profiles", "keybindings",
+];
+const WINDOW_KEYS: [&str; 4] = ["title_bar_visible", "title_template", "confirm_close", "confirm_close_ignore"];
+const FONT_KEYS: [&str; 2] = ["family", "size"];
+const COLOR_KEYS: [&str; 10] = [
+    "foreground", "background", "background_opacity", "cursor", "cursor_foreground", "selection", "selection_foreground", "bold",
+    "palette", "active_preset",
+];
+const SHELL_KEYS: [&str; 7] = ["command", "args", "working_directory", "login", "env", "unset_env", "exit_behavior"];
+const APPEARANCE_KEYS: [&str; 2] = ["light_preset", "dark_preset"];
+const CLIPBOARD_KEYS: [&str; 3] = ["copy_on_select", "paste_warning", "bracketed_paste_programs"];
+const LINKS_KEYS: [&str; 2] = ["hyperlinks", "patterns"];
+const LINK_PATTERN_KEYS: [&str; 2] = ["pattern", "url"];
+const PROFILE_EXTRA_KEYS: [&str; 4] = ["font_family", "font_size", "scrollback_lines", "colors"];
+
+fn is_valid_color(value: &str) -> bool {
+    gdk::RGBA::parse(value).is_ok()
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    value.as_float().or_else(|| value.as_integer().map(|i| i as f64))
+}
+
+pub fn check_config() -> Vec<ConfigDiagnostic> {
+    match config::get_config_path() {
+        Some(path) => check_config_file(&path),
+        None => Vec::new(),
+    }
+}
+
+pub fn check_config_file(path: &Path) -> Vec<ConfigDiagnostic> {
+    if !path.exists() {
+        return Vec::new();
+    }
+    match fs::read_to_string(path) {
+        Ok(contents) => validate_config(path, &contents),
+        Err(e) => vec![ConfigDiagnostic {
+            path: path.to_path_buf(),
+            line: None,
+            key: String::new(),
+            reason: format!("cannot read file: {}", e),
+        }],
+    }
+}
+
+pub fn validate_config(path: &Path, contents: &str) -> Vec<ConfigDiagnostic> {
+    let mut validator = Validator {
+        path,
+        contents,
+        diagnostics: Vec::new(),
+    };
+    if config::is_legacy_format(contents) {
+        validate_legacy(&mut validator);
+    } else {
+        validate_toml(&mut validator);
+    }
+    validator.diagnostics
+}
+
+fn validate_toml(validator: &mut Validator) {
+    let table = match validator.contents.parse::<Table>() {
+        Ok(table) => table,
+        Err(e) => {
+            validator.report_toml_error(&e);
+            return;
+        }
+    };
+
+    // Type mismatches ("size = \"big\"") are caught by the real schema.
+    if let Err(e) = config::parse_app_settings(validator.contents) {
+        validator.report_toml_error(&e);
+        return;
+    }
+
+    check_known_keys(validator, "", &table, &TOP_LEVEL_KEYS);
+    let sections: [(&str, &[&str]); 7] = [
+        ("window", &WINDOW_KEYS),
+        ("font", &FONT_KEYS),
+        ("colors", &COLOR_KEYS),
+        ("shell", &SHELL_KEYS),
+        ("appearance", &APPEARANCE_KEYS),
+        ("clipboard", &CLIPBOARD_KEYS),
+        ("links", &LINKS_KEYS),
+    ];
+    for (name, known) in sections {
+        if let Some(section) = table.get(name).and_then(Value::as_table) {
+            check_known_keys(validator, name, section, known);
+        }
+    }
+    let link_entries = table.get("links").and_then(|links| links.get("patterns")).and_then(Value::as_array);
+    for entry in link_entries.into_iter().flatten().filter_map(Value::as_table) {
+        check_known_keys(validator, "links.patterns", entry, &LINK_PATTERN_KEYS);
+    }
+    let profile_keys: Vec<&str> = SHELL_KEYS.iter().chain(&PROFILE_EXTRA_KEYS).copied().collect();
+    for (name, profile) in table.get("profiles").and_then(Value::as_table).into_iter().flatten() {
+        let Some(profile) = profile.as_table() else { continue };
+        let profile_table = format!("profiles.{}", name);
+        check_known_keys(validator, &profile_table, profile, &profile_keys);
+        if let Some(colors) = profile.get("colors").and_then(Value::as_table) {
+            check_known_keys(validator, &format!("{}.colors", profile_table), colors, &COLOR_KEYS);
+        }
+    }
+
+    if let Some(version) = table.get("version").and_then(Value::as_integer) {
+        if version > CONFIG_VERSION as i64 {
+            let line = validator.key_line("", "version");
+            validator.report(line, "version", format!("unsupported version {}, expected at most {}", version, CONFIG_VERSION));
+        }
+    }
+
+    if let Some(size) = table.get("font").and_then(|font| font.get("size")).and_then(as_number) {
+        if size <= 0.0 {
+            let line = validator.key_line("font", "size");
+            validator.report(line, "font.size", format!("font size must be positive, got {}", size));
+        }
+    }
+
+    if let Some(template) = table.get("window").and_then(|window| window.get("title_template")).and_then(Value::as_str) {
+        for placeholder in template.split('{').skip(1).filter_map(|rest| rest.split_once('}')).map(|(name, _)| name) {
+            if !TITLE_PLACEHOLDERS.contains(&placeholder) {
+                let line = validator.key_line("window", "title_template");
+                validator.report(line, "window.title_template", format!("unknown placeholder {{{}}}, expected one of {{title}}, {{cwd}}, {{process}}", placeholder));
+            }
+        }
+    }
+
+    for key in ["light_preset", "dark_preset"] {
+        if let Some(preset) = table.get("appearance").and_then(|appearance| appearance.get(key)).and_then(Value::as_str) {
+            if ColorSchemePreset::from_name(preset).is_none() {
+                let line = validator.key_line("appearance", key);
+                validator.report(line, &format!("appearance.{}", key), format!("unknown preset \"{}\"", preset));
+            }
+        }
+    }
+
+    if let Some(colors) = table.get("colors").and_then(Value::as_table) {
+        validate_colors(validator, "colors", colors);
+    }
+
+    let profiles = table.get("profiles").and_then(Value::as_table);
+    if let Some(default_profile) = table.get("default_profile").and_then(Value::as_str) {
+        if !profiles.is_some_and(|profiles| profiles.contains_key(default_profile)) {
+            let line = validator.key_line("", "default_profile");
+            validator.report(line, "default_profile", format!("no profile named \"{}\"", default_profile));
+        }
+    }
+    for (name, profile) in profiles.into_iter().flatten() {
+        let Some(profile) = profile.as_table() else { continue };
+        let profile_table = format!("profiles.{}", name);
+        if let Some(size) = profile.get("font_size").and_then(as_number) {
+            if size <= 0.0 {
+                let line = validator.key_line(&profile_table, "font_size");
+                validator.report(line, &format!("{}.font_size", profile_table), format!("font size must be positive, got {}", size));
+            }
+        }
+        if let Some(colors) = profile.get("colors").and_then(Value::as_table) {
+            validate_colors(validator, &format!("{}.colors", profile_table), colors);
+        }
+    }
+
+    let link_patterns = table.get("links").and_then(|links| links.get("patterns")).and_then(Value::as_array);
+    for pattern in link_patterns.into_iter().flatten().filter_map(|entry| entry.get("pattern")).filter_map(Value::as_str) {
+        if let Err(e) = glib::Regex::new(pattern, glib::RegexCompileFlags::MULTILINE, glib::RegexMatchFlags::empty()) {
+            let line = validator.value_line_after(validator.key_line("links.patterns", "pattern"), pattern);
+            validator.report(line, "links.patterns.pattern", format!("invalid pattern \"{}\": {}", pattern, e.message()));
+        }
+    }
+
+    for (name, value) in table.get("keybindings").and_then(Value::as_table).into_iter().flatten() {
+        let key = format!("keybindings.{}", name);
+        let line = validator.key_line("keybindings", name);
+        if keybindings::find_binding(name).is_none() {
+            validator.report(line, &key, format!("unknown action \"{}\"", name));
+            continue;
+        }
+        let accels: Vec<&str> = match value {
+            Value::String(accel) => vec![accel.as_str()],
+            Value::Array(accels) => accels.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        for accel in accels.into_iter().filter(|accel| !accel.is_empty()) {
+            if !is_valid_accelerator(accel) {
+                validator.report(line, &key, format!("invalid accelerator \"{}\"", accel));
+            }
+        }
+    }
+}
+
+/// Reports the keys of `table`, found at `table_name`, that are not in
+/// `known`. The config still loads, but the settings they hold are ignored.
+fn check_known_keys(validator: &mut Validator, table_name: &str, table: &Table, known: &[&str]) {
+    for (key, value) in table {
+        if known.contains(&key.as_str()) {
+            continue;
+        }
+        let path = if table_name.is_empty() { key.clone() } else { format!("{}.{}", table_name, key) };
+        let line = validator.key_line(table_name, key).or_else(|| validator.table_line(&path));
+        let reason = if value.is_table() { "unknown table" } else { "unknown key" };
+        validator.report(line, &path, reason.to_string());
+    }
+}
+
+/// Accepts what `gtk::accelerator_parse` does, such as `<Control><Shift>c`.
+/// That function needs GTK to be initialized, which `--check-config` skips.
+fn is_valid_accelerator(accel: &str) -> bool {
+    const MODIFIERS: [&str; 11] = ["shift", "control", "ctrl", "ctl", "primary", "alt", "mod1", "super", "hyper", "meta", "release"];
+    let mut rest = accel.trim();
+    while let Some(modifier) = rest.strip_prefix('<') {
+        let Some((name, after)) = modifier.split_once('>') else { return false };
+        if !MODIFIERS.contains(&name.to_lowercase().as_str()) {
+            return false;
+        }
+        rest = after;
+    }
+    !rest.is_empty() && gdk::Key::from_name(rest).is_some()
+}
+
+/// Checks a `[colors]` table, or a profile's, named `table_name`.
+fn validate_colors(validator: &mut Validator, table_name: &str, colors: &Table) {
+    for key in ["foreground", "background", "cursor", "cursor_foreground", "selection", "selection_foreground", "bold"] {
+        if let Some(value) = colors.get(key).and_then(Value::as_str) {
+            if !is_valid_color(value) {
+                let line = validator.key_line(table_name, key);
+                validator.report(line, &format!("{}.{}", table_name, key), format!("invalid color \"{}\"", value));
+            }
+        }
+    }
+
+    if let Some(opacity) = colors.get("background_opacity").and_then(as_number) {
+        if !(0.0..=1.0).contains(&opacity) {
+            let line = validator.key_line(table_name, "background_opacity");
+            validator.report(line, &format!("{}.background_opacity", table_name), format!("opacity must be between 0 and 1, got {}", opacity));
+        }
+    }
+
+    if let Some(preset) = colors.get("active_preset").and_then(Value::as_str) {
+        if ColorSchemePreset::from_name(preset).is_none() {
+            let line = validator.key_line(table_name, "active_preset");
+            validator.report(line, &format!("{}.active_preset", table_name), format!("unknown preset \"{}\"", preset));
+        }
+    }
+
+    if let Some(palette) = colors.get("palette").and_then(Value::as_array) {
+        let palette_line = validator.key_line(table_name, "palette");
+        if palette.len() > 16 {
+            validator.report(palette_line, &format!("{}.palette", table_name), format!("palette has {} entries, at most 16 are supported", palette.len()));
+        }
+        for (index, value) in palette.iter().enumerate() {
+            if let Some(color) = value.as_str() {
+                if !color.is_empty() && !is_valid_color(color) {
+                    let line = validator.value_line_after(palette_line, color);
+                    validator.report(line, &format!("{}.palette[{}]", table_name, index), format!("invalid color \"{}\"", color));
+                }
+            }
+        }
+    }
+}
+
+fn validate_legacy(validator: &mut Validator) {
+    let contents = validator.contents;
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = Some(index + 1);
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once('=') else {
+            validator.report(line_number, "", "expected `key = value`".to_string());
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+
+        match key {
+            "titlebar" => {
+                if value != "true" && value != "false" {
+                    validator.report(line_number, key, format!("expected true or false, got \"{}\"", value));
+                }
+            }
+            "font_size" => {
+                if !value.parse::<f64>().is_ok_and(|size| size > 0.0) {
+                    validator.report(line_number, key, format!("expected a positive number, got \"{}\"", value));
+                }
+            }
+            "font_family" | "font_name" => {}
+            "active_preset" => {
+                if ColorSchemePreset::from_name(value).is_none() {
+                    validator.report(line_number, key, format!("unknown preset \"{}\"", value));
+                }
+            }
+            "foreground" | "background" => {
+                if !is_valid_color(value) {
+                    validator.report(line_number, key, format!("invalid color \"{}\"", value));
+                }
+            }
+            "background_opacity" => {
+                if !value.parse::<f64>().is_ok_and(|opacity| (0.0..=1.0).contains(&opacity)) {
+                    validator.report(line_number, key, format!("expected a number between 0 and 1, got \"{}\"", value));
+                }
+            }
+            key if key.starts_with("color") => match key["color".len()..].parse::<usize>() {
+                Ok(index) if index < 16 => {
+                    if !is_valid_color(value) {
+                        validator.report(line_number, key, format!("invalid color \"{}\"", value));
+                    }
+                }
+                Ok(index) => {
+                    validator.report(line_number, key, format!("palette index {} is out of range 0-15", index));
+                }
+                Err(_) => {
+                    validator.report(line_number, key, "unknown key".to_string());
+                }
+            },
+            _ => {
+                validator.report(line_number, key, "unknown key".to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{serialize_app_settings, AppSettings};
+
+    fn validate(contents: &str) -> Vec<(Option<usize>, String, String)> {
+        validate_config(Path::new("test.conf"), contents)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.key, diagnostic.reason))
+            .collect()
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(validate(&serialize_app_settings(&AppSettings::default())), Vec::new());
+    }
+
+    #[test]
+    fn reports_unknown_keys_and_tables() {
+        let diagnostics = validate(
+            "version = 1\n\
+             colour = \"red\"\n\
+             \n\
+             [window]\n\
+             title_bar_visible = true\n\
+             title_bar_hidden = false\n\
+             \n\
+             [profiles.ssh]\n\
+             command = \"ssh host\"\n\
+             font = \"Mono\"\n\
+             \n\
+             [profiles.ssh.colors]\n\
+             forground = \"#ffffff\"\n\
+             \n\
+             [[links.patterns]]\n\
+             pattern = \"JIRA-\\\\d+\"\n\
+             url = \"https://example.com/$0\"\n\
+             link = \"https://example.com\"\n\
+             \n\
+             [themes]\n\
+             dark = \"Nord\"\n",
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Some(2), "colour".to_string(), "unknown key".to_string()),
+                (Some(20), "themes".to_string(), "unknown table".to_string()),
+                (Some(6), "window.title_bar_hidden".to_string(), "unknown key".to_string()),
+                (Some(18), "links.patterns.link".to_string(), "unknown key".to_string()),
+                (Some(10), "profiles.ssh.font".to_string(), "unknown key".to_string()),
+                (Some(13), "profiles.ssh.colors.forground".to_string(), "unknown key".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_values_at_their_lines() {
+        let diagnostics = validate(
+            "[font]\n\
+             family = \"Monospace\"\n\
+             size = -2\n\
+             \n\
+             [colors]\n\
+             foreground = \"not a color\"\n\
+             palette = [\n    \"#000000\",\n    \"nope\",\n]\n\
+             \n\
+             [keybindings]\n\
+             copy = \"<Hyper><Bogus>c\"\n",
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Some(3), "font.size".to_string(), "font size must be positive, got -2".to_string()),
+                (Some(6), "colors.foreground".to_string(), "invalid color \"not a color\"".to_string()),
+                (Some(9), "colors.palette[1]".to_string(), "invalid color \"nope\"".to_string()),
+                (Some(13), "keybindings.copy".to_string(), "invalid accelerator \"<Hyper><Bogus>c\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors_at_their_line() {
+        let diagnostics = validate("[window]\ntitle_bar_visible = true\nconfirm_close = \n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].0, Some(3));
+    }
+
+    #[test]
+    fn reports_unknown_legacy_keys() {
+        let diagnostics = validate("titlebar = true\nfont_size = 12\ncolor16 = #ffffff\nfoo = bar\n");
+        assert_eq!(
+            diagnostics,
+            vec![
+                (Some(3), "color16".to_string(), "palette index 16 is out of range 0-15".to_string()),
+                (Some(4), "foo".to_string(), "unknown key".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_key_lines_in_tables_and_arrays_of_tables() {
+        let contents = "version = 1\n[window]\nconfirm_close = true\n[[links.patterns]]\npattern = \"a\"\n[profiles.ssh]\ncommand = \"ssh\"\n";
+        let validator = Validator {
+            path: Path::new("test.conf"),
+            contents,
+            diagnostics: Vec::new(),
+        };
+        assert_eq!(validator.key_line("", "version"), Some(1));
+        assert_eq!(validator.key_line("window", "confirm_close"), Some(3));
+        assert_eq!(validator.key_line("links.patterns", "pattern"), Some(5));
+        assert_eq!(validator.key_line("profiles.ssh", "command"), Some(7));
+        assert_eq!(validator.key_line("window", "command"), None);
+        assert_eq!(validator.table_line("profiles.ssh"), Some(6));
+    }
+
+    #[test]
+    fn parses_accelerators_like_gtk() {
+        assert!(is_valid_accelerator("<Control><Shift>c"));
+        assert!(is_valid_accelerator("<ctrl><Alt>Page_Up"));
+        assert!(is_valid_accelerator("F11"));
+        assert!(!is_valid_accelerator("<Control><Shift>"));
+        assert!(!is_valid_accelerator("<Control c"));
+        assert!(!is_valid_accelerator("<Bogus>c"));
+        assert!(!is_valid_accelerator(""));
+    }
+}